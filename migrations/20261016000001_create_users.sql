@@ -0,0 +1,30 @@
+-- Accounts and their marketplace roles. IF NOT EXISTS lets the migration be
+-- applied to databases that were created by hand before migrations existed.
+
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS buyers (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS sellers (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS otp_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    otp TEXT NOT NULL DEFAULT lpad(floor(random() * 1000000)::INT::TEXT, 6, '0'),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL DEFAULT NOW() + INTERVAL '15 minutes'
+);
+
+CREATE INDEX IF NOT EXISTS otp_tokens_user_id_idx ON otp_tokens (user_id);
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS categories (
+    category_id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    photo TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS payment_options (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS delivery_options (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_categories (
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category_id INT NOT NULL REFERENCES categories (category_id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, category_id)
+);
@@ -0,0 +1,41 @@
+CREATE TABLE IF NOT EXISTS products (
+    id SERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category_id INT NOT NULL REFERENCES categories (category_id),
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    brand TEXT,
+    condition TEXT NOT NULL,
+    price NUMERIC(12, 2) NOT NULL,
+    phone_number TEXT NOT NULL,
+    color TEXT,
+    shoe_size TEXT,
+    clothing_size TEXT,
+    gender TEXT,
+    material TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS products_user_id_idx ON products (user_id);
+CREATE INDEX IF NOT EXISTS products_category_id_idx ON products (category_id);
+
+CREATE TABLE IF NOT EXISTS product_images (
+    id SERIAL PRIMARY KEY,
+    product_id INT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    position INT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS product_images_product_id_idx ON product_images (product_id);
+
+CREATE TABLE IF NOT EXISTS product_delivery_options (
+    product_id INT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+    delivery_option_id INT NOT NULL REFERENCES delivery_options (id),
+    PRIMARY KEY (product_id, delivery_option_id)
+);
+
+CREATE TABLE IF NOT EXISTS product_payment_options (
+    product_id INT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+    payment_option_id INT NOT NULL REFERENCES payment_options (id),
+    PRIMARY KEY (product_id, payment_option_id)
+);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS blocked_users (
+    blocker_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    blocked_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blocker_id, blocked_id),
+    CHECK (blocker_id <> blocked_id)
+);
+
+CREATE TABLE IF NOT EXISTS reviews (
+    id SERIAL PRIMARY KEY,
+    reviewer_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    seller_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    comment TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (reviewer_id, seller_id),
+    CHECK (reviewer_id <> seller_id)
+);
+
+CREATE INDEX IF NOT EXISTS reviews_seller_id_idx ON reviews (seller_id);
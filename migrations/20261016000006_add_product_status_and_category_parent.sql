@@ -0,0 +1,9 @@
+ALTER TABLE products ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'sold', 'archived'));
+
+CREATE INDEX IF NOT EXISTS products_status_idx ON products (status);
+
+ALTER TABLE categories ADD COLUMN IF NOT EXISTS parent_id INT
+    REFERENCES categories (category_id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS categories_parent_id_idx ON categories (parent_id);
@@ -0,0 +1,10 @@
+-- Drafts may be saved with only a title; everything else is checked on publish.
+ALTER TABLE products DROP CONSTRAINT IF EXISTS products_status_check;
+ALTER TABLE products ADD CONSTRAINT products_status_check
+    CHECK (status IN ('draft', 'active', 'sold', 'archived'));
+
+ALTER TABLE products ALTER COLUMN category_id DROP NOT NULL;
+ALTER TABLE products ALTER COLUMN description DROP NOT NULL;
+ALTER TABLE products ALTER COLUMN condition DROP NOT NULL;
+ALTER TABLE products ALTER COLUMN price DROP NOT NULL;
+ALTER TABLE products ALTER COLUMN phone_number DROP NOT NULL;
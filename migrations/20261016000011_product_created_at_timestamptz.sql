@@ -0,0 +1,2 @@
+-- Existing values were written by NOW() on a UTC server.
+ALTER TABLE products ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
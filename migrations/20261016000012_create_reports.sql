@@ -0,0 +1,16 @@
+-- A report targets either a product or a user, never both.
+CREATE TABLE IF NOT EXISTS reports (
+    id BIGSERIAL PRIMARY KEY,
+    reporter_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    product_id INT REFERENCES products (id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users (id) ON DELETE CASCADE,
+    reason TEXT NOT NULL
+        CHECK (reason IN ('spam', 'fraud', 'prohibited', 'offensive', 'other')),
+    comment TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((product_id IS NULL) <> (user_id IS NULL)),
+    CHECK (reporter_id <> user_id)
+);
+
+CREATE INDEX IF NOT EXISTS reports_product_id_idx ON reports (product_id, reporter_id);
+CREATE INDEX IF NOT EXISTS reports_user_id_idx ON reports (user_id, reporter_id);
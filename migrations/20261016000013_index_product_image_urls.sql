@@ -0,0 +1,3 @@
+-- Uploads are content-addressed, so several images can point at one object.
+-- Deleting an image looks up whether its url is still referenced.
+CREATE INDEX IF NOT EXISTS product_images_url_idx ON product_images (url);
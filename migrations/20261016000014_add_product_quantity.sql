@@ -0,0 +1,5 @@
+ALTER TABLE products ADD COLUMN IF NOT EXISTS quantity INT NOT NULL DEFAULT 1
+    CHECK (quantity >= 0);
+
+-- Listings sold before stock was tracked have nothing left.
+UPDATE products SET quantity = 0 WHERE status = 'sold';
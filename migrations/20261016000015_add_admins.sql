@@ -0,0 +1,19 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+-- Set when a moderator deactivates the account. Unlike an unconfirmed account,
+-- a deactivated one can't be re-activated through a confirmation link.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deactivated_at TIMESTAMPTZ;
+
+-- Audit trail of moderation. Rows outlive the admin and the target, so the
+-- references are cleared rather than cascaded, and removed products have no
+-- foreign key at all.
+CREATE TABLE IF NOT EXISTS admin_actions (
+    id BIGSERIAL PRIMARY KEY,
+    admin_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    action TEXT NOT NULL CHECK (action IN ('deactivate_user', 'remove_product')),
+    target_user_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    target_product_id INT,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS admin_actions_target_user_id_idx ON admin_actions (target_user_id);
@@ -0,0 +1,5 @@
+-- Codes are generated by the application and only their HMAC is stored. Codes
+-- issued before this migration are plaintext and can't be verified anymore.
+DELETE FROM otp_tokens;
+ALTER TABLE otp_tokens ALTER COLUMN otp DROP DEFAULT;
+ALTER TABLE otp_tokens RENAME COLUMN otp TO otp_hash;
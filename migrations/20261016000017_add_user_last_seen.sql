@@ -0,0 +1,2 @@
+-- Refreshed by authenticated requests, at most once a minute per user.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ;
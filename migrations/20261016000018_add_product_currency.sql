@@ -0,0 +1,5 @@
+-- ISO 4217 code of `price`. Prices entered before this were all in hryvnias.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS currency TEXT NOT NULL DEFAULT 'UAH'
+    CHECK (currency IN ('UAH', 'USD', 'EUR', 'PLN', 'GBP'));
+
+CREATE INDEX IF NOT EXISTS products_currency_price_idx ON products (currency, price);
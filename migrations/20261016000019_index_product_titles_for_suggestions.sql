@@ -0,0 +1,6 @@
+-- Trigram indexes let `ILIKE '%...%'` on titles and brands use an index, which
+-- search suggestions and the substring listing search both rely on.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS products_title_trgm_idx ON products USING GIN (title gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS products_brand_trgm_idx ON products USING GIN (brand gin_trgm_ops);
@@ -0,0 +1,18 @@
+-- Brands were stored as typed. Collapse whitespace, drop empty ones and give
+-- every case variant the most common spelling, which is what new products get.
+UPDATE products
+SET brand = NULLIF(btrim(regexp_replace(brand, '\s+', ' ', 'g')), '')
+WHERE brand IS NOT NULL;
+
+UPDATE products p
+SET brand = canonical.brand
+FROM (
+    SELECT DISTINCT ON (lower(brand)) lower(brand) AS key, brand
+    FROM products
+    WHERE brand IS NOT NULL
+    GROUP BY brand
+    ORDER BY lower(brand), COUNT(*) DESC, brand
+) canonical
+WHERE lower(p.brand) = canonical.key AND p.brand <> canonical.brand;
+
+CREATE INDEX IF NOT EXISTS products_lower_brand_idx ON products (lower(brand));
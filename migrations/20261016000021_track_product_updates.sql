@@ -0,0 +1,35 @@
+-- When a product last changed. Existing products count as unchanged since they
+-- were created.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ;
+UPDATE products SET updated_at = created_at WHERE updated_at IS NULL;
+ALTER TABLE products
+    ALTER COLUMN updated_at SET DEFAULT NOW(),
+    ALTER COLUMN updated_at SET NOT NULL;
+
+-- Every UPDATE of a product moves it forward, whichever query runs it.
+CREATE OR REPLACE FUNCTION set_product_updated_at() RETURNS trigger AS $$
+BEGIN
+    NEW.updated_at = NOW();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS products_set_updated_at ON products;
+CREATE TRIGGER products_set_updated_at
+    BEFORE UPDATE ON products
+    FOR EACH ROW EXECUTE FUNCTION set_product_updated_at();
+
+-- Photos live in their own table, so adding, removing or reordering one has
+-- to touch the product explicitly.
+CREATE OR REPLACE FUNCTION touch_product_of_image() RETURNS trigger AS $$
+BEGIN
+    UPDATE products SET updated_at = NOW()
+    WHERE id = CASE WHEN TG_OP = 'DELETE' THEN OLD.product_id ELSE NEW.product_id END;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS product_images_touch_product ON product_images;
+CREATE TRIGGER product_images_touch_product
+    AFTER INSERT OR UPDATE OR DELETE ON product_images
+    FOR EACH ROW EXECUTE FUNCTION touch_product_of_image();
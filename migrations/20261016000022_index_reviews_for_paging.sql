@@ -0,0 +1,3 @@
+-- Reviews are paged newest first by id within a seller.
+DROP INDEX IF EXISTS reviews_seller_id_idx;
+CREATE INDEX IF NOT EXISTS reviews_seller_id_id_idx ON reviews (seller_id, id DESC);
@@ -0,0 +1,20 @@
+-- At most one short video per product, next to its photos.
+CREATE TABLE IF NOT EXISTS product_videos (
+    product_id INT PRIMARY KEY REFERENCES products (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Like photos, changing the video counts as changing the product.
+CREATE OR REPLACE FUNCTION touch_product_of_video() RETURNS trigger AS $$
+BEGIN
+    UPDATE products SET updated_at = NOW()
+    WHERE id = CASE WHEN TG_OP = 'DELETE' THEN OLD.product_id ELSE NEW.product_id END;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS product_videos_touch_product ON product_videos;
+CREATE TRIGGER product_videos_touch_product
+    AFTER INSERT OR UPDATE OR DELETE ON product_videos
+    FOR EACH ROW EXECUTE FUNCTION touch_product_of_video();
@@ -0,0 +1,241 @@
+use crate::errors::{AppError, ErrorEnvelope, route_not_found};
+use crate::handlers::admin::{deactivate_user, remove_product};
+use crate::handlers::auth::{
+    SignupConfig, SignupRequest, confirm, login, logout, otp_verify, refresh_token,
+    resend_confirmation, reset_password, signup, update_password, verify_token,
+};
+use crate::handlers::health::health;
+use crate::handlers::products::{
+    add_images, categories as product_categories, create as product_create, delete_image,
+    delete_video, delivery_options, edit as product_edit, get_brands, get_clothing_sizes,
+    get_colors, get_genders, get_materials, get_product_by_id, get_products, get_products_batch,
+    get_products_count, get_shoe_sizes, get_similar_products, payment_options, publish, put_video,
+    reorder_images, suggest, update_status as product_update_status,
+};
+use crate::handlers::reports::{report_product, report_user};
+use crate::handlers::users::{
+    block_user, categories as user_categories, change_email, create as user_create, create_review,
+    delete_me, get_me, get_my_products, get_reviews, get_user, unblock_user,
+};
+use crate::middleware::rate_limit::{RateLimits, rate_limit};
+use crate::services::password::PasswordConfig;
+use crate::services::token::JwtConfig;
+use actix_cors::Cors;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::error::JsonPayloadError;
+use actix_web::middleware::from_fn;
+use actix_web::{App, web};
+use sqlx::PgPool;
+use tracing_actix_web::TracingLogger;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::handlers::admin::deactivate_user,
+        crate::handlers::admin::remove_product,
+        crate::handlers::auth::signup,
+        crate::handlers::auth::confirm,
+        crate::handlers::auth::resend_confirmation,
+        crate::handlers::auth::login,
+        crate::handlers::auth::logout,
+        crate::handlers::auth::refresh_token,
+        crate::handlers::auth::verify_token,
+        crate::handlers::auth::reset_password,
+        crate::handlers::auth::otp_verify,
+        crate::handlers::auth::update_password,
+        crate::handlers::products::categories,
+        crate::handlers::products::payment_options,
+        crate::handlers::products::delivery_options,
+        crate::handlers::products::create,
+        crate::handlers::products::get_products,
+        crate::handlers::products::get_products_count,
+        crate::handlers::products::get_product_by_id,
+        crate::handlers::products::get_similar_products,
+        crate::handlers::products::get_products_batch,
+        crate::handlers::products::suggest,
+        crate::handlers::products::get_brands,
+        crate::handlers::products::update_status,
+        crate::handlers::products::edit,
+        crate::handlers::products::publish,
+        crate::handlers::products::add_images,
+        crate::handlers::products::reorder_images,
+        crate::handlers::products::delete_image,
+        crate::handlers::products::put_video,
+        crate::handlers::products::delete_video,
+        crate::handlers::products::get_colors,
+        crate::handlers::products::get_shoe_sizes,
+        crate::handlers::products::get_clothing_sizes,
+        crate::handlers::products::get_genders,
+        crate::handlers::products::get_materials,
+        crate::handlers::reports::report_product,
+        crate::handlers::reports::report_user,
+        crate::handlers::users::create_review,
+    ),
+    components(
+        schemas(SignupRequest, ErrorEnvelope)
+    ),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "Auth", description = "Registration, login, tokens and password recovery."),
+        (name = "Products", description = "Listings, their images and the option lists used to fill them in."),
+        (name = "Users", description = "Profiles, roles, blocking and seller reviews."),
+        (name = "Reports", description = "Flagging products and users for moderation."),
+        (name = "Admin", description = "Moderation tools. Every action is recorded in an audit log.")
+    )
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// What the app is built from. `main` reads it from the environment once and
+/// clones it into every worker; tests build it by hand.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub jwt_config: JwtConfig,
+    pub password_config: PasswordConfig,
+    pub signup_config: SignupConfig,
+    pub rate_limits: web::Data<RateLimits>,
+    /// Accept requests from any origin.
+    pub dev_mode: bool,
+    pub allowed_origins: Vec<String>,
+}
+
+/// The whole API: middleware, shared data and every route.
+pub fn build_app(
+    state: AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let cors = if state.dev_mode {
+        Cors::default().allow_any_origin()
+    } else {
+        state
+            .allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+    };
+
+    App::new()
+        // Innermost, so rejected requests still get CORS headers and are traced.
+        .wrap(from_fn(rate_limit))
+        .wrap(cors.allow_any_method().allow_any_header())
+        .wrap(TracingLogger::default())
+        .app_data(web::Data::new(state.pool))
+        .app_data(web::Data::new(state.jwt_config))
+        .app_data(web::Data::new(state.password_config))
+        .app_data(web::Data::new(state.signup_config))
+        .app_data(state.rate_limits)
+        .app_data(
+            web::JsonConfig::default().error_handler(|err, _| match err {
+                JsonPayloadError::Overflow { .. }
+                | JsonPayloadError::OverflowKnownLength { .. } => {
+                    AppError::PayloadTooLarge(err.to_string()).into()
+                }
+                _ => AppError::BadRequest(err.to_string()).into(),
+            }),
+        )
+        .app_data(
+            web::QueryConfig::default()
+                .error_handler(|err, _| AppError::BadRequest(err.to_string()).into()),
+        )
+        .app_data(
+            web::PathConfig::default()
+                .error_handler(|err, _| AppError::BadRequest(err.to_string()).into()),
+        )
+        .default_service(web::to(route_not_found))
+        .service(health)
+        .service(
+            SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-doc/openapi.json", ApiDoc::openapi()),
+        )
+        .service(
+            web::scope("/api/v1")
+                .service(
+                    web::scope("/auth")
+                        .service(signup)
+                        .service(confirm)
+                        .service(resend_confirmation)
+                        .service(login)
+                        .service(logout)
+                        .service(refresh_token)
+                        .service(verify_token)
+                        .service(reset_password)
+                        .service(otp_verify)
+                        .service(update_password),
+                )
+                .service(
+                    web::scope("/users")
+                        .service(user_create)
+                        .service(user_categories)
+                        .service(change_email)
+                        .service(delete_me)
+                        .service(block_user)
+                        .service(unblock_user)
+                        .service(report_user)
+                        .service(create_review)
+                        .service(get_reviews)
+                        // `/me` must be registered before `/{id}` so it isn't parsed as an id.
+                        .service(get_me)
+                        .service(get_my_products)
+                        .service(get_user),
+                )
+                .service(
+                    web::scope("/admin")
+                        .service(deactivate_user)
+                        .service(remove_product),
+                )
+                .service(
+                    web::scope("/products")
+                        .service(product_categories)
+                        .service(payment_options)
+                        .service(delivery_options)
+                        .service(product_create)
+                        .service(get_products)
+                        .service(get_products_count)
+                        .service(get_products_batch)
+                        .service(suggest)
+                        .service(get_brands)
+                        .service(product_update_status)
+                        .service(product_edit)
+                        .service(publish)
+                        .service(add_images)
+                        .service(reorder_images)
+                        .service(delete_image)
+                        .service(put_video)
+                        .service(delete_video)
+                        .service(report_product)
+                        .service(get_colors)
+                        .service(get_shoe_sizes)
+                        .service(get_clothing_sizes)
+                        .service(get_genders)
+                        .service(get_materials)
+                        .service(get_similar_products)
+                        .service(get_product_by_id),
+                ),
+        )
+}
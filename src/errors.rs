@@ -0,0 +1,196 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+/// Error returned by every handler. Each variant maps to one HTTP status and is
+/// rendered as `{"error": {"code": ..., "message": ...}}`.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    MethodNotAllowed(String),
+    Conflict(String),
+    Gone(String),
+    PayloadTooLarge(String),
+    TooManyRequests {
+        message: String,
+        retry_after: Option<i64>,
+    },
+    /// 400 listing every invalid field. `message` joins their messages for
+    /// clients that only show that.
+    Validation {
+        message: String,
+        errors: Vec<FieldError>,
+    },
+    Internal,
+}
+
+/// One invalid field of a request.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Name of the field as the client sent it, e.g. `price`.
+    pub field: String,
+    pub message: String,
+}
+
+/// Problems found while checking a request, so they can be reported together
+/// rather than one round trip at a time.
+#[derive(Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    /// Records `message` unless `field` already has an error; the first
+    /// problem found with a field is the most specific one.
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        if !self.0.iter().any(|error| error.field == field) {
+            self.0.push(FieldError {
+                field: field.to_string(),
+                message: message.into(),
+            });
+        }
+    }
+
+    /// The value of `result`, or `None` after recording its error against `field`.
+    pub fn check<T>(&mut self, field: &str, result: Result<T, AppError>) -> Option<T> {
+        result
+            .map_err(|error| self.add(field, error.to_string()))
+            .ok()
+    }
+
+    pub fn into_result(self) -> Result<(), AppError> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let message = self
+            .0
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        Err(AppError::Validation {
+            message,
+            errors: self.0,
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine-readable error code, e.g. `not_found`.
+    code: &'static str,
+    /// Human-readable description of the error.
+    message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorEnvelope {
+    error: ErrorBody,
+    /// Every invalid field, on `validation_failed` errors.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+impl AppError {
+    /// Logs the underlying error and hides it from the client.
+    pub fn internal<E: fmt::Display>(error: E) -> Self {
+        tracing::error!(error = %error, "Internal server error");
+        AppError::Internal
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::MethodNotAllowed(_) => "method_not_allowed",
+            AppError::Conflict(_) => "conflict",
+            AppError::Gone(_) => "gone",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::Validation { .. } => "validation_failed",
+            AppError::Internal => "internal_error",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::MethodNotAllowed(message)
+            | AppError::Conflict(message)
+            | AppError::Gone(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::TooManyRequests { message, .. }
+            | AppError::Validation { message, .. } => message,
+            AppError::Internal => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+
+        if let AppError::TooManyRequests {
+            retry_after: Some(retry_after),
+            ..
+        } = self
+        {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+
+        response.json(ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code(),
+                message: self.message().to_string(),
+            },
+            errors: match self {
+                AppError::Validation { errors, .. } => errors.clone(),
+                _ => Vec::new(),
+            },
+        })
+    }
+}
+
+/// Default service of the app. Routes are registered with method guards, so a
+/// request for a known path with the wrong method ends up here too.
+pub async fn route_not_found(req: HttpRequest) -> HttpResponse {
+    let error = if req.resource_map().has_resource(req.path()) {
+        AppError::MethodNotAllowed(format!("{} is not allowed here", req.method()))
+    } else {
+        AppError::NotFound("No such route".into())
+    };
+
+    error.error_response()
+}
@@ -0,0 +1,222 @@
+use crate::errors::{AppError, ErrorEnvelope};
+use crate::handlers::auth::AdminUser;
+use crate::handlers::products::delete_unreferenced_uploads;
+use actix_web::{HttpResponse, Responder, post, web};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const MAX_REASON_LENGTH: usize = 1000;
+
+/// Kinds of entries in `admin_actions`.
+#[derive(Debug, Clone, Copy)]
+enum AdminAction {
+    DeactivateUser,
+    RemoveProduct,
+}
+
+impl fmt::Display for AdminAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AdminAction::DeactivateUser => write!(f, "deactivate_user"),
+            AdminAction::RemoveProduct => write!(f, "remove_product"),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AdminActionRequest {
+    /// Why the action was taken. Stored in the audit log.
+    reason: String,
+}
+
+impl AdminActionRequest {
+    fn reason(&self) -> Result<&str, AppError> {
+        let reason = self.reason.trim();
+
+        if reason.is_empty() {
+            return Err(AppError::BadRequest("A reason is required".into()));
+        }
+        if reason.chars().count() > MAX_REASON_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "Reason must be at most {} characters",
+                MAX_REASON_LENGTH
+            )));
+        }
+
+        Ok(reason)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AdminActionResponse {
+    /// Id of the audit log entry.
+    action_id: i64,
+}
+
+async fn record_action(
+    tx: &mut Transaction<'_, Postgres>,
+    admin_id: Uuid,
+    action: AdminAction,
+    target_user_id: Uuid,
+    target_product_id: Option<i32>,
+    reason: &str,
+) -> Result<i64, AppError> {
+    sqlx::query_scalar(
+        "INSERT INTO admin_actions (admin_id, action, target_user_id, target_product_id, reason)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id",
+    )
+    .bind(admin_id)
+    .bind(action.to_string())
+    .bind(target_user_id)
+    .bind(target_product_id)
+    .bind(reason)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(AppError::internal)
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/admin",
+    tag = "Admin",
+    params(("id" = Uuid, Path, description = "User id")),
+    request_body = AdminActionRequest,
+    responses(
+        (status = 200, description = "User deactivated and signed out", body = AdminActionResponse),
+        (status = 400, description = "Missing reason or deactivating yourself", body = ErrorEnvelope),
+        (status = 403, description = "Not an admin", body = ErrorEnvelope),
+        (status = 404, description = "User not found", body = ErrorEnvelope),
+        (status = 409, description = "User is already deactivated", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/users/{id}/deactivate")]
+pub async fn deactivate_user(
+    admin: AdminUser,
+    path: web::Path<Uuid>,
+    req: web::Json<AdminActionRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let user_id = path.into_inner();
+    let reason = req.reason()?;
+
+    if user_id == admin.0.sub {
+        return Err(AppError::BadRequest(
+            "You cannot deactivate your own account".into(),
+        ));
+    }
+
+    let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+    let deactivated: Option<bool> =
+        sqlx::query_scalar("SELECT deactivated_at IS NOT NULL FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    match deactivated {
+        None => return Err(AppError::NotFound("User not found".into())),
+        Some(true) => {
+            return Err(AppError::Conflict("User is already deactivated".into()));
+        }
+        Some(false) => {}
+    }
+
+    // Access tokens stop working through the `active` check in
+    // `AuthenticatedUser`; refresh tokens are revoked outright.
+    sqlx::query("UPDATE users SET active = false, deactivated_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    sqlx::query("DELETE FROM refresh_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    let action_id = record_action(
+        &mut tx,
+        admin.0.sub,
+        AdminAction::DeactivateUser,
+        user_id,
+        None,
+        reason,
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(AdminActionResponse { action_id }))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/admin",
+    tag = "Admin",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body = AdminActionRequest,
+    responses(
+        (status = 200, description = "Product and its images deleted", body = AdminActionResponse),
+        (status = 400, description = "Missing reason", body = ErrorEnvelope),
+        (status = 403, description = "Not an admin", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/products/{id}/remove")]
+pub async fn remove_product(
+    admin: AdminUser,
+    path: web::Path<i32>,
+    req: web::Json<AdminActionRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+    let reason = req.reason()?;
+
+    let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+    let owner: Uuid = sqlx::query_scalar("SELECT user_id FROM products WHERE id = $1 FOR UPDATE")
+        .bind(product_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound("Product not found".into()))?;
+
+    let upload_urls: Vec<String> = sqlx::query_scalar(
+        "SELECT url FROM product_images WHERE product_id = $1
+         UNION ALL
+         SELECT url FROM product_videos WHERE product_id = $1",
+    )
+    .bind(product_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    // Images, the video, options and reports of the product go with it.
+    sqlx::query("DELETE FROM products WHERE id = $1")
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    let action_id = record_action(
+        &mut tx,
+        admin.0.sub,
+        AdminAction::RemoveProduct,
+        owner,
+        Some(product_id),
+        reason,
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    delete_unreferenced_uploads(db_pool.get_ref(), upload_urls).await;
+
+    Ok(HttpResponse::Ok().json(AdminActionResponse { action_id }))
+}
@@ -1,18 +1,15 @@
-use actix_web::error::ErrorUnauthorized;
-use actix_web::{Error, FromRequest, HttpRequest, HttpResponse, Responder, get, patch, post, web};
-use argon2::password_hash::{PasswordHash, PasswordVerifier, SaltString, rand_core::OsRng};
-use argon2::{Argon2, PasswordHasher};
-use chrono::Utc;
-use futures_util::future::{Ready, ready};
-use jsonwebtoken::{
-    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, encode,
-};
-use lettre::message::SinglePart;
-use lettre::message::header::ContentType;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
+use crate::errors::{AppError, ErrorEnvelope};
+use crate::services::email::{send_confirmation_email, send_password_reset_email};
+use crate::services::password::PasswordConfig;
+use crate::services::token::{Claims, JwtConfig, TokenInput, TokenType, generate_otp};
+use actix_web::http::header;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, get, patch, post, web};
+use chrono::{DateTime, Utc};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use jsonwebtoken::errors::ErrorKind;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Row};
 use std::env;
 use utoipa::ToSchema;
@@ -27,523 +24,799 @@ pub struct SignupRequest {
 }
 
 #[derive(Serialize, ToSchema)]
-pub struct SignupResponse {
+pub struct MessageResponse {
     message: String,
-    token: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: Uuid,
-    email: String,
-    exp: usize,
+impl MessageResponse {
+    fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
 }
 
-struct EmailConfig {
-    host: String,
-    from: String,
-    user: String,
-    password: String,
+#[derive(Serialize, ToSchema)]
+pub struct SignupResponse {
+    message: String,
+    /// Access token, only sent when the account was activated right away. The
+    /// confirmation token goes to the email address alone, so the link proves
+    /// the caller owns it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    /// Only sent when the account was activated right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
 }
 
-impl EmailConfig {
-    fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(Self {
-            host: env::var("EMAIL_HOST")?,
-            from: env::var("EMAIL_FROM")?,
-            user: env::var("EMAIL_USER")?,
-            password: env::var("EMAIL_PASSWORD")?,
-        })
-    }
+/// How new accounts get activated.
+#[derive(Clone, Copy, Default)]
+pub struct SignupConfig {
+    /// Activates accounts at signup instead of emailing a confirmation link.
+    /// For local development and CI, where there is no SMTP server.
+    pub auto_confirm: bool,
 }
 
-async fn send_confirmation_email(
-    user_email: &str,
-    html_body: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let config = EmailConfig::from_env()?;
+const MAX_FAILED_LOGINS: i64 = 5;
+const FAILED_LOGIN_WINDOW_MINUTES: i64 = 15;
+const MAX_OTP_ATTEMPTS: i32 = 5;
+/// How stale `last_seen_at` may get before an authenticated request refreshes it.
+const LAST_SEEN_RESOLUTION_SECS: f64 = 60.0;
 
-    let email = Message::builder()
-        .from(config.from.parse()?)
-        .to(user_email.parse()?)
-        .subject("Confirm your registration")
-        .singlepart(
-            SinglePart::builder()
-                .header(ContentType::TEXT_HTML)
-                .body(html_body.to_string()),
-        )?;
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
 
-    let creds = Credentials::new(config.user, config.password);
+async fn issue_refresh_token<'e, E>(
+    executor: E,
+    jwt: &JwtConfig,
+    user_id: Uuid,
+    email: &str,
+) -> Result<String, AppError>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let expires_at = jwt.expires_at(TokenType::Refresh);
+    let token = jwt.create_token(TokenInput {
+        user_id,
+        email,
+        token_type: TokenType::Refresh,
+        is_admin: false,
+    })?;
+
+    sqlx::query("INSERT INTO refresh_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(expires_at)
+        .execute(executor)
+        .await
+        .map_err(AppError::internal)?;
 
-    let mailer = SmtpTransport::relay(&config.host)?
-        .credentials(creds)
-        .build();
+    Ok(token)
+}
 
-    match mailer.send(&email) {
-        Ok(_) => {
-            println!("Email sent successfully!");
-        }
-        Err(e) => {
-            eprintln!("Failed to send email: {:?}", e);
-            return Err(actix_web::error::ErrorInternalServerError("Failed to send email").into());
-        }
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub fn normalize_email(email: &str) -> Result<String, AppError> {
+    let email = email.trim().to_lowercase();
+
+    let email_regex = Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap();
+
+    if !email_regex.is_match(&email) {
+        return Err(AppError::BadRequest("Invalid email format".into()));
     }
 
-    Ok(())
+    Ok(email)
 }
 
-#[derive(Serialize, Deserialize, Clone, ToSchema)]
-pub(super) enum ErrorResponse {
-    /// When Todo is not found by search term.
-    NotFound(String),
-    /// When there is a conflict storing a new todo.
-    Conflict(String),
-    /// When todo endpoint was called without correct credentials
-    Unauthorized(String),
+pub fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err(AppError::BadRequest(
+            "Password must contain at least one letter and one digit".into(),
+        ));
+    }
+
+    Ok(())
 }
 
 #[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
     request_body = SignupRequest,
     responses(
-        (status = 201, description = "User created"),
-        (status = 409, description = "User already exists", body = ErrorResponse)
+        (status = 201, description = "User created. With AUTO_CONFIRM set it is already active and gets an access and a refresh token", body = SignupResponse),
+        (status = 400, description = "Invalid email or weak password", body = ErrorEnvelope),
+        (status = 409, description = "User already exists", body = ErrorEnvelope)
     )
 )]
 #[post("/register")]
 pub async fn signup(
     user: web::Json<SignupRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
+    jwt: web::Data<JwtConfig>,
+    passwords: web::Data<PasswordConfig>,
+    config: web::Data<SignupConfig>,
+) -> Result<impl Responder, AppError> {
     // Тут може бути логіка реєстрації, перевірка у базі, хешування пароля і т.д.
+    let email = normalize_email(&user.email)?;
+
     let existing_user: Option<(String,)> =
         sqlx::query_as("SELECT email FROM users WHERE email = $1")
-            .bind(&user.email)
+            .bind(&email)
             .fetch_optional(db_pool.get_ref())
             .await
-            .unwrap();
+            .map_err(AppError::internal)?;
 
     if existing_user.is_some() {
-        return Ok(HttpResponse::Conflict().body("User with this email already exists"));
+        return Err(AppError::Conflict(
+            "User with this email already exists".into(),
+        ));
     }
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(user.password.as_bytes(), &salt)
-        .unwrap()
-        .to_string();
+    validate_password(&user.password)?;
+
+    let password_hash = passwords.hash(&user.password)?;
 
     let user_row = sqlx::query(
-        "INSERT INTO users (first_name, last_name, email, password) VALUES ($1, $2, $3, $4) RETURNING id",
+        "INSERT INTO users (first_name, last_name, email, password, active) VALUES ($1, $2, $3, $4, $5) RETURNING id",
     )
     .bind(&user.first_name)
     .bind(&user.last_name)
-    .bind(&user.email)
+    .bind(&email)
     .bind(&password_hash)
+    .bind(config.auto_confirm)
     .fetch_one(db_pool.get_ref())
-    .await.map_err(actix_web::error::ErrorInternalServerError)?;
-
-    let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::days(7))
-        .unwrap()
-        .timestamp() as usize;
+    .await.map_err(AppError::internal)?;
 
     let user_id: Uuid = user_row.try_get("id").unwrap();
 
-    let claims = Claims {
-        sub: user_id,
-        email: user.email.clone(),
-        exp: expiration,
-    };
+    if config.auto_confirm {
+        let token = jwt.create_token(TokenInput {
+            user_id,
+            email: &email,
+            token_type: TokenType::Access,
+            is_admin: false,
+        })?;
+        let refresh = issue_refresh_token(db_pool.get_ref(), &jwt, user_id, &email).await?;
+
+        return Ok(HttpResponse::Created().json(SignupResponse {
+            message: "Registration successful, the account is active".into(),
+            token: Some(token),
+            refresh_token: Some(refresh),
+        }));
+    }
 
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".into());
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
-    .unwrap();
-
-    let body = format!(
-        "<!DOCTYPE html>
-<html lang=\"uk\">
-  <head>
-    <meta charset=\"UTF-8\" />
-    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\" />
-    <title>Підтвердження пошти</title>
-    <style>
-      body {{
-        margin: 0;
-        padding: 0;
-        font-family: Arial, sans-serif;
-        background-color: #ffffff;
-        color: #333333;
-      }}
-
-      .container {{
-        max-width: 600px;
-        margin: auto;
-        background: #ffffff;
-        border-radius: 12px;
-        overflow: hidden;
-        box-shadow: 0 2px 6px rgba(0, 0, 0, 0.1);
-      }}
-
-      .header {{
-        background: linear-gradient(to right, #f7941d, #fbd38d);
-        text-align: center;
-        padding: 30px 30px 0px;
-      }}
-
-      .header img {{
-        max-width: 250px;
-        height: auto;
-        display: block;
-        margin: 0 auto;
-      }}
-
-      .content {{
-        padding: 20px;
-        text-align: center;
-      }}
-
-      .content h1 {{
-        font-size: 22px;
-        margin-bottom: 10px;
-      }}
-
-      .content p {{
-        font-size: 16px;
-        margin-bottom: 20px;
-      }}
-
-      .btn {{
-        display: inline-block;
-        padding: 12px 24px;
-        background: linear-gradient(to right, #f7941d, #fbd38d);
-        color: #ffffff;
-        text-decoration: none;
-        font-weight: bold;
-        border-radius: 25px;
-        font-size: 16px;
-      }}
-
-      .footer {{
-        font-size: 14px;
-        padding: 0 20px 20px;
-        color: #666666;
-        text-align: center;
-      }}
-
-      @media (max-width: 600px) {{
-        .content h1 {{
-          font-size: 20px;
-        }}
-
-        .content p {{
-          font-size: 15px;
-        }}
-
-        .btn {{
-          font-size: 15px;
-          padding: 10px 20px;
-        }}
-      }}
-    </style>
-  </head>
-  <body>
-    <div class=\"container\">
-      <div class=\"header\">
-        <img
-          src=\"https://marketplace-bucket-mmsj1bcf.s3.eu-central-1.amazonaws.com/email/email.png\"
-          alt=\"Shum маркетплейс\"
-        />
-      </div>
-      <div class=\"content\">
-        <h1>Підтвердіть Вашу пошту</h1>
-        <p>Привіт, {}!</p>
-        <p>
-          Дякуємо, що приєдналися до маркетплейсу Shum. Щоб завершити
-          реєстрацію, підтвердіть свою електронну адресу, натиснувши на кнопку
-          нижче.
-        </p>
-        <a href=\"{}/{}\" class=\"btn\">Підтвердити</a>
-      </div>
-      <div class=\"footer\">
-        <p>
-          Якщо ви не створювали обліковий запис, просто проігноруйте цей лист.
-        </p>
-        <p>З повагою, Команда Shum.</p>
-      </div>
-    </div>
-  </body>
-</html>",
-        &user.first_name,
-        env::var("EMAIL_REGISTRATION_URL").unwrap(),
-        token
-    );
-
-    send_confirmation_email(user.email.as_str(), &body).await?;
-
-    Ok(HttpResponse::Ok().json(SignupResponse {
+    let token = jwt.create_token(TokenInput {
+        user_id,
+        email: &email,
+        token_type: TokenType::Confirmation,
+        is_admin: false,
+    })?;
+
+    // The account already exists at this point; if the email fails the user can
+    // ask for it again through /resend-confirmation.
+    if let Err(e) = send_confirmation_email(&email, &user.first_name, &token).await {
+        tracing::error!(error = %e, "Failed to send confirmation email");
+    }
+
+    Ok(HttpResponse::Created().json(SignupResponse {
         message: "Registration successful".into(),
-        token,
+        token: None,
+        refresh_token: None,
     }))
 }
 
+async fn confirm_email_change(db_pool: &PgPool, claims: &Claims) -> Result<HttpResponse, AppError> {
+    let taken: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&claims.email)
+        .fetch_optional(db_pool)
+        .await
+        .map_err(AppError::internal)?;
+
+    if taken.is_some_and(|id| id != claims.sub) {
+        return Err(AppError::Conflict("Email is already in use".into()));
+    }
+
+    let result = sqlx::query(
+        "UPDATE users SET email = pending_email, pending_email = NULL WHERE id = $1 AND pending_email = $2",
+    )
+    .bind(claims.sub)
+    .bind(&claims.email)
+    .execute(db_pool)
+    .await
+    .map_err(AppError::internal)?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Invalid confirmation link".into()));
+    }
+
+    Ok(HttpResponse::Ok().json(MessageResponse::new("Email successfully changed")))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    params(("token" = String, Path, description = "Token from the confirmation email")),
+    responses(
+        (status = 200, description = "Email confirmed or changed", body = MessageResponse),
+        (status = 302, description = "Email confirmed, redirecting to EMAIL_CONFIRMED_REDIRECT_URL"),
+        (status = 400, description = "Invalid confirmation link", body = ErrorEnvelope),
+        (status = 409, description = "The new email is already in use", body = ErrorEnvelope),
+        (status = 410, description = "Confirmation link has expired", body = ErrorEnvelope)
+    )
+)]
 #[get("/confirm/{token}")]
-async fn confirm(token: web::Path<String>, db_pool: web::Data<PgPool>) -> impl Responder {
-    let token = token.into_inner();
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".into());
+pub async fn confirm(
+    token: web::Path<String>,
+    db_pool: web::Data<PgPool>,
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let claims = match jwt.decode_token(&token) {
+        Ok(claims) if claims.token_type == TokenType::EmailChange => {
+            return confirm_email_change(db_pool.get_ref(), &claims).await;
+        }
+        // Links sent before confirmation tokens got their own type carry access tokens.
+        Ok(claims)
+            if matches!(
+                claims.token_type,
+                TokenType::Confirmation | TokenType::Access
+            ) =>
+        {
+            claims
+        }
+        Err(error) if matches!(error.kind(), ErrorKind::ExpiredSignature) => {
+            return Err(AppError::Gone("Confirmation link has expired".into()));
+        }
+        _ => {
+            return Err(AppError::BadRequest("Invalid confirmation link".into()));
+        }
+    };
 
-    let mut validation = Validation::default();
-    validation.leeway = 0;
+    let result = sqlx::query(
+        "UPDATE users SET active = true
+         WHERE id = $1 AND email = $2 AND deactivated_at IS NULL",
+    )
+    .bind(claims.sub)
+    .bind(&claims.email)
+    .execute(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Invalid confirmation link".into()));
+    }
 
-    let decoding_key = DecodingKey::from_secret(secret.as_bytes());
+    if let Ok(redirect_url) = env::var("EMAIL_CONFIRMED_REDIRECT_URL") {
+        return Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, redirect_url))
+            .finish());
+    }
 
-    let token_data: Result<TokenData<Claims>, jsonwebtoken::errors::Error> =
-        decode(&token, &decoding_key, &validation);
+    Ok(HttpResponse::Ok().json(MessageResponse::new("Email successfully confirmed")))
+}
 
-    match token_data {
-        Ok(token_data) => {
-            let email = token_data.claims.email.clone();
+#[derive(Deserialize, ToSchema)]
+pub struct ResendConfirmationRequest {
+    email: String,
+}
 
-            let _ = sqlx::query("UPDATE users SET active = true WHERE email = $1")
-                .bind(&email)
-                .execute(db_pool.get_ref())
-                .await;
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = ResendConfirmationRequest,
+    responses(
+        (status = 200, description = "Sent if an unconfirmed account exists", body = MessageResponse)
+    )
+)]
+#[post("/resend-confirmation")]
+pub async fn resend_confirmation(
+    req: web::Json<ResendConfirmationRequest>,
+    db_pool: web::Data<PgPool>,
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let email = normalize_email(&req.email)?;
+
+    let row = sqlx::query(
+        "SELECT id, first_name FROM users
+         WHERE email = $1 AND active = false AND deactivated_at IS NULL",
+    )
+    .bind(&email)
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
 
-            HttpResponse::Ok().body("Email successfully confirmed!")
+    if let Some(user) = row {
+        let user_id: Uuid = user.try_get("id").map_err(AppError::internal)?;
+        let first_name: String = user.try_get("first_name").map_err(AppError::internal)?;
+
+        let token = jwt.create_token(TokenInput {
+            user_id,
+            email: &email,
+            token_type: TokenType::Confirmation,
+            is_admin: false,
+        })?;
+
+        // Failures are only logged so the response doesn't reveal whether the account exists.
+        if let Err(e) = send_confirmation_email(&email, &first_name, &token).await {
+            tracing::error!(error = %e, "Failed to resend confirmation email");
         }
-        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
     }
+
+    Ok(HttpResponse::Ok().json(MessageResponse::new(
+        "If an unconfirmed account with this email exists, a confirmation email has been sent",
+    )))
 }
 
-#[derive(Deserialize)]
-struct LoginRequest {
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
     email: String,
     password: String,
 }
 
-#[derive(serde::Serialize)]
-struct LoginResponse {
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
     token: String,
+    refresh_token: String,
+}
+
+/// Returns how many seconds the caller has to wait before trying to log in
+/// again, or `None` if the email is not locked out.
+async fn login_lockout(db_pool: &PgPool, email: &str) -> Result<Option<i64>, AppError> {
+    let window_start = Utc::now() - chrono::Duration::minutes(FAILED_LOGIN_WINDOW_MINUTES);
+
+    let (failures, oldest): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+        "SELECT COUNT(*), MIN(attempted_at) FROM login_attempts WHERE email = $1 AND attempted_at > $2",
+    )
+    .bind(email)
+    .bind(window_start)
+    .fetch_one(db_pool)
+    .await
+    .map_err(AppError::internal)?;
+
+    match oldest {
+        Some(oldest) if failures >= MAX_FAILED_LOGINS => {
+            let unlocks_at = oldest + chrono::Duration::minutes(FAILED_LOGIN_WINDOW_MINUTES);
+            Ok(Some((unlocks_at - Utc::now()).num_seconds().max(1)))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn record_failed_login(db_pool: &PgPool, email: &str) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO login_attempts (email) VALUES ($1)")
+        .bind(email)
+        .execute(db_pool)
+        .await
+        .map_err(AppError::internal)?;
+
+    Ok(())
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access and refresh tokens", body = LoginResponse),
+        (status = 401, description = "Invalid credentials or email not confirmed", body = ErrorEnvelope),
+        (status = 429, description = "Too many failed attempts, see Retry-After", body = ErrorEnvelope)
+    )
+)]
 #[post("/login")]
-async fn login(
+pub async fn login(
     creds: web::Json<LoginRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
-    let row = sqlx::query("SELECT id, password, email, active FROM users WHERE email = $1")
-        .bind(&creds.email)
-        .fetch_optional(db_pool.get_ref())
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+    jwt: web::Data<JwtConfig>,
+    passwords: web::Data<PasswordConfig>,
+) -> Result<impl Responder, AppError> {
+    let email = normalize_email(&creds.email)?;
+
+    if let Some(retry_after) = login_lockout(db_pool.get_ref(), &email).await? {
+        return Err(AppError::TooManyRequests {
+            message: "Too many failed login attempts".into(),
+            retry_after: Some(retry_after),
+        });
+    }
+
+    let row = sqlx::query(
+        "SELECT id, password, email, active, is_admin, deactivated_at IS NOT NULL AS deactivated
+         FROM users WHERE email = $1",
+    )
+    .bind(&email)
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
 
     if let Some(user) = row {
-        let active: bool = user
-            .try_get("active")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+        let user_password: String = user.try_get("password").map_err(AppError::internal)?;
 
-        if !active {
-            return Ok(HttpResponse::Unauthorized().body("Email not confirmed"));
-        }
+        if passwords.verify(&creds.password, &user_password)? {
+            // The account state is only revealed to someone who knows the password.
+            let active: bool = user.try_get("active").map_err(AppError::internal)?;
+            let deactivated: bool = user.try_get("deactivated").map_err(AppError::internal)?;
 
-        let user_password: String = user
-            .try_get("password")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            if deactivated {
+                return Err(AppError::Unauthorized(
+                    "Account has been deactivated".into(),
+                ));
+            }
+            if !active {
+                return Err(AppError::Unauthorized("Email not confirmed".into()));
+            }
 
-        let parsed_hash = PasswordHash::new(&user_password)
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            let user_id: Uuid = user.try_get("id").map_err(AppError::internal)?;
+            let is_admin: bool = user.try_get("is_admin").map_err(AppError::internal)?;
+
+            if passwords.needs_rehash(&user_password) {
+                upgrade_password_hash(
+                    db_pool.get_ref(),
+                    &passwords,
+                    user_id,
+                    &creds.password,
+                    &user_password,
+                )
+                .await;
+            }
 
-        let verified = Argon2::default()
-            .verify_password(creds.password.as_bytes(), &parsed_hash)
-            .is_ok();
+            sqlx::query("DELETE FROM login_attempts WHERE email = $1")
+                .bind(&email)
+                .execute(db_pool.get_ref())
+                .await
+                .map_err(AppError::internal)?;
 
-        if verified {
-            let user_id: Uuid = user
-                .try_get("id")
-                .map_err(actix_web::error::ErrorInternalServerError)?;
+            let token = jwt.create_token(TokenInput {
+                user_id,
+                email: &email,
+                token_type: TokenType::Access,
+                is_admin,
+            })?;
 
-            let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string());
+            let refresh = issue_refresh_token(db_pool.get_ref(), &jwt, user_id, &email).await?;
 
-            let exp = Utc::now().timestamp() as usize + 7 * 24 * 60 * 60;
+            return Ok(HttpResponse::Ok().json(LoginResponse {
+                token,
+                refresh_token: refresh,
+            }));
+        }
+    }
 
-            let claims = Claims {
-                sub: user_id,
-                email: creds.email.clone(),
-                exp,
-            };
+    record_failed_login(db_pool.get_ref(), &email).await?;
 
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(secret.as_ref()),
-            )
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+    Err(AppError::Unauthorized("Invalid credentials".into()))
+}
 
-            return Ok(HttpResponse::Ok().json(LoginResponse { token }));
+/// Re-hashes a password stored with weaker Argon2 settings than the current
+/// ones. Login is the only time the plain password is at hand. A failure is
+/// logged and doesn't stop the login.
+async fn upgrade_password_hash(
+    db_pool: &PgPool,
+    passwords: &PasswordConfig,
+    user_id: Uuid,
+    password: &str,
+    old_hash: &str,
+) {
+    let new_hash = match passwords.hash(password) {
+        Ok(new_hash) => new_hash,
+        Err(e) => {
+            tracing::warn!(error = %e, %user_id, "Failed to upgrade password hash");
+            return;
         }
+    };
+
+    // Matching the old hash keeps a concurrent password change from being undone.
+    if let Err(e) = sqlx::query("UPDATE users SET password = $1 WHERE id = $2 AND password = $3")
+        .bind(new_hash)
+        .bind(user_id)
+        .bind(old_hash)
+        .execute(db_pool)
+        .await
+    {
+        tracing::warn!(error = %e, %user_id, "Failed to upgrade password hash");
     }
+}
 
-    Ok(HttpResponse::Unauthorized().body("Invalid credentials"))
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    refresh_token: String,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Refresh token revoked")
+    )
+)]
 #[post("/logout")]
-async fn logout() -> impl Responder {
-    HttpResponse::Ok().body("Logged out (token should be removed on client)")
-}
+pub async fn logout(
+    req: web::Json<RefreshRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    sqlx::query("DELETE FROM refresh_tokens WHERE token_hash = $1")
+        .bind(hash_token(&req.refresh_token))
+        .execute(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
 
-#[derive(Deserialize)]
-struct RefreshRequest {
-    refresh_token: String,
+    Ok(HttpResponse::Ok().body("Logged out"))
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access token and rotated refresh token", body = LoginResponse),
+        (status = 401, description = "Invalid, expired or already used refresh token", body = ErrorEnvelope)
+    )
+)]
 #[post("/refresh-token")]
-async fn refresh_token(req: web::Json<RefreshRequest>) -> impl Responder {
-    let secret = env::var("JWT_SECRET").unwrap_or("secret".into());
-
-    let decoded = decode::<Claims>(
-        &req.refresh_token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    );
-
-    match decoded {
-        Ok(data) => {
-            let new_exp = Utc::now().timestamp() as usize + 7 * 24 * 60 * 60;
-            let claims = Claims {
-                sub: data.claims.sub,
-                email: data.claims.email,
-                exp: new_exp,
-            };
-
-            let new_token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(secret.as_ref()),
+pub async fn refresh_token(
+    req: web::Json<RefreshRequest>,
+    db_pool: web::Data<PgPool>,
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    match jwt.decode_token(&req.refresh_token) {
+        Ok(claims) if claims.token_type == TokenType::Refresh => {
+            let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+            // Refresh tokens are single use: the presented one is revoked and replaced.
+            let revoked = sqlx::query(
+                "DELETE FROM refresh_tokens WHERE token_hash = $1 AND user_id = $2 AND expires_at > NOW()",
             )
-            .unwrap();
+            .bind(hash_token(&req.refresh_token))
+            .bind(claims.sub)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+            if revoked.rows_affected() == 0 {
+                return Err(AppError::Unauthorized("Invalid token".into()));
+            }
+
+            let is_admin: bool = sqlx::query_scalar("SELECT is_admin FROM users WHERE id = $1")
+                .bind(claims.sub)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(AppError::internal)?
+                .ok_or_else(|| AppError::Unauthorized("Invalid token".into()))?;
+
+            let token = jwt.create_token(TokenInput {
+                user_id: claims.sub,
+                email: &claims.email,
+                token_type: TokenType::Access,
+                is_admin,
+            })?;
+
+            let refresh = issue_refresh_token(&mut *tx, &jwt, claims.sub, &claims.email).await?;
 
-            HttpResponse::Ok().json(json!({ "token": new_token }))
+            tx.commit().await.map_err(AppError::internal)?;
+
+            Ok(HttpResponse::Ok().json(LoginResponse {
+                token,
+                refresh_token: refresh,
+            }))
         }
-        Err(_) => HttpResponse::Unauthorized().body("Invalid token"),
+        _ => Err(AppError::Unauthorized("Invalid token".into())),
     }
 }
 
-#[derive(Deserialize)]
-struct ResetPasswordRequest {
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyTokenRequest {
+    token: String,
+}
+
+/// Lets other services check an access token issued by this API. Accepts exactly
+/// what `AuthenticatedUser` accepts.
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = VerifyTokenRequest,
+    responses(
+        (status = 200, description = "The token's claims", body = Claims),
+        (status = 401, description = "Invalid or expired token, or the user is inactive", body = ErrorEnvelope)
+    )
+)]
+#[post("/verify")]
+pub async fn verify_token(
+    req: web::Json<VerifyTokenRequest>,
+    db_pool: web::Data<PgPool>,
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let claims = jwt.decode_access_token(&req.token)?;
+
+    let active: Option<bool> = sqlx::query_scalar("SELECT active FROM users WHERE id = $1")
+        .bind(claims.sub)
+        .fetch_optional(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    if active != Some(true) {
+        return Err(AppError::Unauthorized("User not found or inactive".into()));
+    }
+
+    Ok(HttpResponse::Ok().json(claims))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
     email: String,
 }
 
-#[derive(Serialize)]
-struct ResetPasswordResponse {
-    otp: String,
+#[derive(Serialize, ToSchema)]
+pub struct ResetPasswordResponse {
+    message: String,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Code sent if the account exists", body = ResetPasswordResponse)
+    )
+)]
 #[post("/reset-password")]
-async fn reset_password(
+pub async fn reset_password(
     req: web::Json<ResetPasswordRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
-    let email = req.email.clone();
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let email = normalize_email(&req.email)?;
 
     let row = sqlx::query("SELECT id FROM users WHERE email = $1")
         .bind(&email)
         .fetch_optional(db_pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     if let Some(user) = row {
-        let user_id: Uuid = user
-            .try_get("id")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        let otp = sqlx::query("INSERT INTO otp_tokens (user_id) VALUES ($1) RETURNING otp")
-            .bind(user_id)
-            .fetch_one(db_pool.get_ref())
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        let otp_token = otp
-            .try_get("otp")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        let body = format!(
-            "You requested to reset your password.\n\
-             Otp: {}\n\n\
-             If you did not request this, please ignore this email.",
-            otp_token
-        );
-
-        send_confirmation_email(&email, &body).await?;
+        let user_id: Uuid = user.try_get("id").map_err(AppError::internal)?;
+
+        let otp = generate_otp();
+
+        // A new code replaces the old ones but keeps their failed attempts, so
+        // requesting another code doesn't lift an OTP lockout.
+        sqlx::query(
+            "WITH old AS (
+                 DELETE FROM otp_tokens WHERE user_id = $1 RETURNING attempts, expires_at
+             )
+             INSERT INTO otp_tokens (user_id, otp_hash, expires_at, attempts)
+             SELECT $1, $2, NOW() + make_interval(secs => $3),
+                    COALESCE(MAX(attempts) FILTER (WHERE expires_at >= NOW()), 0)
+             FROM old",
+        )
+        .bind(user_id)
+        .bind(jwt.hash_otp(user_id, &otp))
+        .bind(jwt.otp_ttl().num_seconds() as f64)
+        .execute(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
 
-        return Ok(HttpResponse::Ok().json(ResetPasswordResponse { otp: otp_token }));
+        // Sent in the background and only logged on failure: an error or a slow
+        // mail server must not tell registered emails apart from unknown ones.
+        tokio::spawn(async move {
+            if let Err(e) = send_password_reset_email(&email, &otp).await {
+                tracing::warn!(error = %e, "Failed to send password reset email");
+            }
+        });
     }
 
-    Ok(HttpResponse::Unauthorized().body("User not found"))
+    // Same response whether or not the account exists, so the endpoint can't be
+    // used to enumerate registered emails.
+    Ok(HttpResponse::Ok().json(ResetPasswordResponse {
+        message: "If an account with this email exists, a reset code has been sent".into(),
+    }))
+}
+
+/// Removes codes that can no longer be used. Run periodically from `main`.
+pub async fn delete_expired_otps(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    sqlx::query("DELETE FROM otp_tokens WHERE expires_at < NOW()")
+        .execute(db_pool)
+        .await
+        .map(|result| result.rows_affected())
 }
 
-#[derive(Deserialize)]
-struct OtpRequest {
+#[derive(Deserialize, ToSchema)]
+pub struct OtpRequest {
     email: String,
     otp: String,
 }
 
-#[derive(Serialize)]
-struct OtpResponse {
+#[derive(Serialize, ToSchema)]
+pub struct OtpResponse {
     message: String,
     token: String,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = OtpRequest,
+    responses(
+        (status = 200, description = "Code accepted, returns an access token", body = OtpResponse),
+        (status = 401, description = "Invalid or expired code", body = ErrorEnvelope),
+        (status = 429, description = "Too many invalid attempts", body = ErrorEnvelope)
+    )
+)]
 #[post("/otp")]
-async fn otp_verify(
+pub async fn otp_verify(
     req: web::Json<OtpRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
-    let email = req.email.clone();
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let email = normalize_email(&req.email)?;
 
-    let user_row = sqlx::query("SELECT id, first_name, last_name FROM users WHERE email = $1")
+    let user_row = sqlx::query("SELECT id, is_admin FROM users WHERE email = $1")
         .bind(&email)
         .fetch_optional(db_pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     if let Some(user) = user_row {
-        let user_id: Uuid = user
-            .try_get("id")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+        let user_id: Uuid = user.try_get("id").map_err(AppError::internal)?;
+        let is_admin: bool = user.try_get("is_admin").map_err(AppError::internal)?;
+
+        let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+        let otp_row = sqlx::query(
+            "SELECT id, otp_hash, attempts FROM otp_tokens
+             WHERE user_id = $1 AND expires_at >= NOW()
+             ORDER BY expires_at DESC
+             LIMIT 1
+             FOR UPDATE",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+        if let Some(otp_row) = otp_row {
+            let otp_id: i32 = otp_row.try_get("id").map_err(AppError::internal)?;
+            let otp_hash: String = otp_row.try_get("otp_hash").map_err(AppError::internal)?;
+            let attempts: i32 = otp_row.try_get("attempts").map_err(AppError::internal)?;
+
+            if attempts >= MAX_OTP_ATTEMPTS {
+                return Err(AppError::TooManyRequests {
+                    message: "Too many invalid OTP attempts".into(),
+                    retry_after: None,
+                });
+            }
 
-        let otp = req.otp.clone();
+            if !jwt.verify_otp(user_id, &req.otp, &otp_hash) {
+                sqlx::query("UPDATE otp_tokens SET attempts = attempts + 1 WHERE id = $1")
+                    .bind(otp_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(AppError::internal)?;
 
-        let otp_row = sqlx::query("SELECT user_id FROM otp_tokens WHERE otp = $1 AND user_id = $2 AND expires_at >= NOW()")
-            .bind(&otp)
-            .bind(&user_id)
-            .fetch_optional(db_pool.get_ref())
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        if otp_row.is_some() {
-            let expiration = chrono::Utc::now()
-                .checked_add_signed(chrono::Duration::days(7))
-                .unwrap()
-                .timestamp() as usize;
-
-            let claims = Claims {
-                sub: user_id,
-                email: email.clone(),
-                exp: expiration,
-            };
+                tx.commit().await.map_err(AppError::internal)?;
+
+                return Err(AppError::Unauthorized("Invalid credentials".into()));
+            }
 
-            let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".into());
+            sqlx::query("DELETE FROM otp_tokens WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::internal)?;
 
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(secret.as_ref()),
-            )
-            .unwrap();
+            let token = jwt.create_token(TokenInput {
+                user_id,
+                email: &email,
+                token_type: TokenType::Access,
+                is_admin,
+            })?;
+
+            tx.commit().await.map_err(AppError::internal)?;
 
             return Ok(HttpResponse::Ok().json(OtpResponse {
                 message: "Login successful".into(),
@@ -552,69 +825,161 @@ async fn otp_verify(
         }
     }
 
-    Ok(HttpResponse::Unauthorized().body("Invalid credentials"))
+    Err(AppError::Unauthorized("Invalid credentials".into()))
 }
 
 #[derive(Debug)]
 pub struct AuthenticatedUser(pub Claims);
 
+impl AuthenticatedUser {
+    pub(crate) fn decode(req: &HttpRequest) -> Result<Claims, AppError> {
+        let Some(jwt) = req.app_data::<web::Data<JwtConfig>>() else {
+            return Err(AppError::internal("JWT is not configured"));
+        };
+
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|auth_str| auth_str.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::Unauthorized("Missing or malformed token".into()))?;
+
+        jwt.decode_access_token(token)
+    }
+}
+
 impl FromRequest for AuthenticatedUser {
-    type Error = Error;
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let claims = Self::decode(req);
+        let db_pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+        Box::pin(async move {
+            let claims = claims?;
+            let db_pool =
+                db_pool.ok_or_else(|| AppError::internal("Database is not configured"))?;
+
+            // A valid signature is not enough: deleted or deactivated users must lose
+            // access immediately rather than when their token expires.
+            let status: Option<(bool, bool)> = sqlx::query_as(
+                "SELECT active,
+                        last_seen_at IS NULL
+                            OR last_seen_at < NOW() - make_interval(secs => $2)
+                 FROM users WHERE id = $1",
+            )
+            .bind(claims.sub)
+            .bind(LAST_SEEN_RESOLUTION_SECS)
+            .fetch_optional(db_pool.get_ref())
+            .await
+            .map_err(AppError::internal)?;
+
+            let Some((true, last_seen_stale)) = status else {
+                return Err(AppError::Unauthorized("User not found or inactive".into()));
+            };
+
+            // Only written when stale so most requests stay read-only.
+            if last_seen_stale {
+                sqlx::query("UPDATE users SET last_seen_at = NOW() WHERE id = $1")
+                    .bind(claims.sub)
+                    .execute(db_pool.get_ref())
+                    .await
+                    .map_err(AppError::internal)?;
+            }
+
+            Ok(AuthenticatedUser(claims))
+        })
+    }
+}
+
+/// The claims of a valid access token, without checking that the user still
+/// exists. Only for endpoints that must keep answering once the account is gone.
+#[derive(Debug)]
+pub struct TokenClaims(pub Claims);
+
+impl FromRequest for TokenClaims {
+    type Error = AppError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-        let auth_header = req.headers().get("Authorization");
-
-        if let Some(header_value) = auth_header {
-            if let Ok(auth_str) = header_value.to_str() {
-                if auth_str.starts_with("Bearer ") {
-                    let token = &auth_str[7..];
-
-                    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".into());
-                    let key = DecodingKey::from_secret(secret.as_bytes());
-
-                    let validation = Validation::new(Algorithm::HS256);
-                    return match decode::<Claims>(token, &key, &validation) {
-                        Ok(token_data) => ready(Ok(AuthenticatedUser(token_data.claims))),
-                        Err(_) => ready(Err(ErrorUnauthorized("Invalid token"))),
-                    };
-                }
+        ready(AuthenticatedUser::decode(req).map(TokenClaims))
+    }
+}
+
+/// An authenticated user with `is_admin` set. The flag is read from the database
+/// rather than the token, so revoking it takes effect immediately.
+#[derive(Debug)]
+pub struct AdminUser(pub Claims);
+
+impl FromRequest for AdminUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let user = AuthenticatedUser::from_request(req, payload);
+        let db_pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+        Box::pin(async move {
+            let AuthenticatedUser(claims) = user.await?;
+            let db_pool =
+                db_pool.ok_or_else(|| AppError::internal("Database is not configured"))?;
+
+            // The user can be deleted between the two queries.
+            let is_admin: bool = sqlx::query_scalar("SELECT is_admin FROM users WHERE id = $1")
+                .bind(claims.sub)
+                .fetch_optional(db_pool.get_ref())
+                .await
+                .map_err(AppError::internal)?
+                .ok_or_else(|| AppError::Unauthorized("User not found or inactive".into()))?;
+
+            if !is_admin {
+                return Err(AppError::Forbidden("Admin access required".into()));
             }
-        }
 
-        ready(Err(ErrorUnauthorized("Missing or malformed token")))
+            Ok(AdminUser(claims))
+        })
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdatePasswordRequest {
     pub password: String,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/auth",
+    tag = "Auth",
+    request_body = UpdatePasswordRequest,
+    responses(
+        (status = 200, description = "Password updated"),
+        (status = 400, description = "Password too weak", body = ErrorEnvelope),
+        (status = 401, description = "Missing or invalid token", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
 #[patch("/update-password")]
-async fn update_password(
+pub async fn update_password(
     user: AuthenticatedUser,
     req: web::Json<UpdatePasswordRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
+    passwords: web::Data<PasswordConfig>,
+) -> Result<impl Responder, AppError> {
     let user_id = &user.0.sub;
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.password.as_bytes(), &salt)
-        .unwrap()
-        .to_string();
+    validate_password(&req.password)?;
+
+    let password_hash = passwords.hash(&req.password)?;
 
     let update_password = sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
         .bind(password_hash)
         .bind(user_id)
         .execute(db_pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     if update_password.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().body("User not found"));
+        return Err(AppError::NotFound("User not found".into()));
     }
 
     Ok(HttpResponse::Ok().body("Password updated successfully"))
@@ -0,0 +1,14 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use serde_json::json;
+use sqlx::PgPool;
+
+#[get("/health")]
+async fn health(db_pool: web::Data<PgPool>) -> impl Responder {
+    match sqlx::query("SELECT 1").execute(db_pool.get_ref()).await {
+        Ok(_) => HttpResponse::Ok().json(json!({ "status": "ok" })),
+        Err(e) => {
+            tracing::warn!(error = %e, "Health check failed");
+            HttpResponse::ServiceUnavailable().json(json!({ "status": "degraded" }))
+        }
+    }
+}
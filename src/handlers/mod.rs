@@ -1,3 +1,6 @@
+pub mod admin;
 pub mod auth;
+pub mod health;
 pub mod products;
+pub mod reports;
 pub mod users;
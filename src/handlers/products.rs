@@ -1,50 +1,135 @@
+use crate::errors::{AppError, ErrorEnvelope, FieldErrors};
 use crate::handlers::auth::AuthenticatedUser;
-use crate::services::s3::{AWS_MARKETPLACE_BUCKET, AWS_REGION, MAX_FILE_SIZE, upload_to_s3};
-use actix_multipart::Multipart;
-use actix_web::{HttpResponse, Responder, get, post, web};
+use crate::services::images::{EncodedImage, strip_metadata, validate_dimensions};
+use crate::services::s3::{
+    AWS_MARKETPLACE_BUCKET, MAX_REQUEST_UPLOAD_BYTES, MAX_UPLOAD_BYTES, build_s3_url,
+    delete_from_s3, is_allowed_image_type, key_from_url, upload_to_s3,
+};
+use crate::services::videos::{EncodedVideo, MAX_VIDEO_BYTES};
+use actix_multipart::{Field, Multipart};
+use actix_web::dev::Payload;
+use actix_web::http::header::{
+    self, AcceptLanguage, CacheControl, CacheDirective, ContentType, EntityTag, Header,
+    IfNoneMatch, Preference,
+};
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, Responder, delete, get, patch, post, put, web,
+};
 use bigdecimal::BigDecimal;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Utc};
 use futures_util::StreamExt;
 use mime_guess::from_path;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgArguments;
 use sqlx::types::Json;
-use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Row, Transaction};
+use sqlx::{Arguments, FromRow, PgExecutor, PgPool, Postgres, QueryBuilder, Row, Transaction};
 use std::collections::HashMap;
 use std::fmt;
+use std::future::{Ready, ready};
 use std::str::FromStr;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 struct Category {
     category_id: i32,
+    parent_id: Option<i32>,
     name: String,
     photo: String,
+    /// Active products in the category and its subcategories. Only with `with_counts`.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product_count: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct CategoriesResponse {
     categories: Vec<Category>,
 }
 
-#[get("/categories")]
-async fn categories(db_pool: web::Data<PgPool>) -> Result<impl Responder, actix_web::Error> {
-    let rows = sqlx::query_as::<_, Category>(
-        "SELECT category_id, name, photo FROM categories ORDER BY name",
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CategoryQuery {
+    /// Children of this category; top-level categories when omitted.
+    parent_id: Option<i32>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Adds `product_count` to every category.
+    with_counts: Option<bool>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(CategoryQuery),
+    responses(
+        (status = 200, description = "Categories with photo urls", body = CategoriesResponse)
     )
-    .fetch_all(db_pool.get_ref())
-    .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+)]
+#[get("/categories")]
+pub async fn categories(
+    db_pool: web::Data<PgPool>,
+    query: web::Query<CategoryQuery>,
+) -> Result<impl Responder, AppError> {
+    let with_counts = query.with_counts.unwrap_or(false);
+
+    let mut qb = if with_counts {
+        // `tree` pairs every category with itself and all of its descendants.
+        QueryBuilder::new(
+            "WITH RECURSIVE tree AS (
+                SELECT category_id AS root_id, category_id FROM categories
+                UNION ALL
+                SELECT tree.root_id, c.category_id
+                FROM categories c
+                JOIN tree ON c.parent_id = tree.category_id
+            )
+            SELECT c.category_id, c.parent_id, c.name, c.photo, COUNT(p.id) AS product_count
+            FROM categories c
+            LEFT JOIN tree ON tree.root_id = c.category_id
+            LEFT JOIN products p ON p.category_id = tree.category_id AND p.status = 'active'",
+        )
+    } else {
+        QueryBuilder::new("SELECT c.category_id, c.parent_id, c.name, c.photo FROM categories c")
+    };
+
+    match query.parent_id {
+        Some(parent_id) => {
+            qb.push(" WHERE c.parent_id = ");
+            qb.push_bind(parent_id);
+        }
+        None => {
+            qb.push(" WHERE c.parent_id IS NULL");
+        }
+    }
+
+    if with_counts {
+        qb.push(" GROUP BY c.category_id");
+    }
+
+    qb.push(" ORDER BY c.name");
+
+    if let Some(limit) = query.limit {
+        qb.push(" LIMIT ");
+        qb.push_bind(limit);
+    }
+
+    if let Some(offset) = query.offset {
+        qb.push(" OFFSET ");
+        qb.push_bind(offset);
+    }
+
+    let rows = qb
+        .build_query_as::<Category>()
+        .fetch_all(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
 
     let categories: Vec<Category> = rows
         .into_iter()
         .map(|mut c| {
-            c.photo = format!(
-                "https://{}.s3.{}.amazonaws.com/media/{}",
-                AWS_MARKETPLACE_BUCKET.as_str(),
-                AWS_REGION.as_str(),
-                c.photo
-            );
+            c.photo = build_s3_url(&format!("media/{}", c.photo));
             c
         })
         .collect();
@@ -54,66 +139,80 @@ async fn categories(db_pool: web::Data<PgPool>) -> Result<impl Responder, actix_
         .json(CategoriesResponse { categories }))
 }
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 struct PaymentOptions {
     id: i32,
     name: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct PaymentOptionsRequest {
     payment_options: Vec<PaymentOptions>,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    responses(
+        (status = 200, description = "Available payment options", body = PaymentOptionsRequest)
+    )
+)]
 #[get("/payment-options")]
-async fn payment_options(db_pool: web::Data<PgPool>) -> Result<impl Responder, actix_web::Error> {
+pub async fn payment_options(db_pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
     let payment_options =
         sqlx::query_as::<_, PaymentOptions>("SELECT id, name FROM payment_options ORDER BY id")
             .fetch_all(db_pool.get_ref())
             .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            .map_err(AppError::internal)?;
 
     Ok(HttpResponse::Ok()
         .content_type("application/json; charset=utf-8")
         .json(PaymentOptionsRequest { payment_options }))
 }
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 struct DeliveryOptions {
     id: i32,
     name: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct DeliveryOptionsRequest {
-    delivery_options: Vec<PaymentOptions>,
+    delivery_options: Vec<DeliveryOptions>,
 }
 
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    responses(
+        (status = 200, description = "Available delivery options", body = DeliveryOptionsRequest)
+    )
+)]
 #[get("/delivery-options")]
-async fn delivery_options(db_pool: web::Data<PgPool>) -> Result<impl Responder, actix_web::Error> {
+pub async fn delivery_options(db_pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
     let delivery_options =
-        sqlx::query_as::<_, PaymentOptions>("SELECT id, name FROM delivery_options ORDER BY id")
+        sqlx::query_as::<_, DeliveryOptions>("SELECT id, name FROM delivery_options ORDER BY id")
             .fetch_all(db_pool.get_ref())
             .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            .map_err(AppError::internal)?;
 
     Ok(HttpResponse::Ok()
         .content_type("application/json; charset=utf-8")
         .json(DeliveryOptionsRequest { delivery_options }))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum ProductCondition {
-    NEW,
-    USED,
+    New,
+    Used,
 }
 
 impl fmt::Display for ProductCondition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            ProductCondition::NEW => write!(f, "NEW"),
-            ProductCondition::USED => write!(f, "USED"),
+            ProductCondition::New => write!(f, "NEW"),
+            ProductCondition::Used => write!(f, "USED"),
         }
     }
 }
@@ -123,22 +222,85 @@ impl FromStr for ProductCondition {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_uppercase().as_str() {
-            "NEW" => Ok(ProductCondition::NEW),
-            "USED" => Ok(ProductCondition::USED),
+            "NEW" => Ok(ProductCondition::New),
+            "USED" => Ok(ProductCondition::Used),
+            _ => Err(()),
+        }
+    }
+}
+
+/// ISO 4217 codes a price can be given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    Uah,
+    Usd,
+    Eur,
+    Pln,
+    Gbp,
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Currency::Uah => write!(f, "UAH"),
+            Currency::Usd => write!(f, "USD"),
+            Currency::Eur => write!(f, "EUR"),
+            Currency::Pln => write!(f, "PLN"),
+            Currency::Gbp => write!(f, "GBP"),
+        }
+    }
+}
+
+impl FromStr for Currency {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "UAH" => Ok(Currency::Uah),
+            "USD" => Ok(Currency::Usd),
+            "EUR" => Ok(Currency::Eur),
+            "PLN" => Ok(Currency::Pln),
+            "GBP" => Ok(Currency::Gbp),
             _ => Err(()),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductStatus {
+    Draft,
+    Active,
+    Sold,
+    Archived,
+}
+
+impl fmt::Display for ProductStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ProductStatus::Draft => write!(f, "draft"),
+            ProductStatus::Active => write!(f, "active"),
+            ProductStatus::Sold => write!(f, "sold"),
+            ProductStatus::Archived => write!(f, "archived"),
+        }
+    }
+}
+
+/// Drafts may leave out everything but the title; the rest is enforced by
+/// `PublishFields::validate` when the product is published.
 #[derive(Deserialize)]
 pub struct CreateProductRequest {
+    pub draft: bool,
     pub title: String,
-    pub description: String,
-    pub category_id: i32,
+    pub description: Option<String>,
+    pub category_id: Option<i32>,
     pub brand: Option<String>,
-    pub condition: ProductCondition,
-    pub price: f64,
-    pub phone_number: String,
+    pub condition: Option<ProductCondition>,
+    pub price: Option<f64>,
+    pub currency: Currency,
+    pub phone_number: Option<String>,
+    pub quantity: i32,
     pub delivery_option_ids: Vec<i32>,
     pub payment_option_ids: Vec<i32>,
     pub color: Option<String>,
@@ -146,87 +308,316 @@ pub struct CreateProductRequest {
     pub clothing_size: Option<String>,
     pub gender: Option<String>,
     pub material: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl CreateProductRequest {
+    fn publish_fields(&self, photo_count: usize) -> PublishFields {
+        PublishFields {
+            description: self.description.clone(),
+            category_id: self.category_id,
+            condition: self.condition.map(|condition| condition.to_string()),
+            price: self.price,
+            phone_number: self.phone_number.clone(),
+            photo_count: photo_count as i64,
+        }
+    }
+}
+
+/// Everything a published product needs that a draft can do without.
+#[derive(FromRow)]
+struct PublishFields {
+    description: Option<String>,
+    category_id: Option<i32>,
+    condition: Option<String>,
+    price: Option<f64>,
+    phone_number: Option<String>,
+    photo_count: i64,
+}
+
+impl PublishFields {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.description.is_none() {
+            errors.add("description", "Missing description");
+        }
+        if self.phone_number.is_none() {
+            errors.add("phone_number", "Phone number is missing");
+        }
+        match self.price {
+            None => errors.add("price", "Missing price"),
+            Some(price) if price <= 0.0 => errors.add("price", "Price must be positive"),
+            Some(_) => {}
+        }
+        if self.category_id.is_none() {
+            errors.add("category_id", "Missing category");
+        }
+        if self.condition.is_none() {
+            errors.add("condition", "Missing condition");
+        }
+        if self.photo_count == 0 {
+            errors.add("photos", "At least one photo is required");
+        }
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        let mut errors = FieldErrors::default();
+        self.check(&mut errors);
+        errors.into_result()
+    }
+}
+
+async fn load_publish_fields(
+    tx: &mut Transaction<'_, Postgres>,
+    product_id: i32,
+) -> Result<PublishFields, AppError> {
+    sqlx::query_as::<_, PublishFields>(
+        "SELECT p.description, p.category_id, p.condition, p.price::float8 AS price,
+                p.phone_number,
+                (SELECT COUNT(*) FROM product_images WHERE product_id = p.id) AS photo_count
+         FROM products p
+         WHERE p.id = $1",
+    )
+    .bind(product_id)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(AppError::internal)
+}
+
+fn validate_coordinates(latitude: f64, longitude: f64) -> Result<(), AppError> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(AppError::BadRequest("Invalid coordinates".into()));
+    }
+
+    Ok(())
+}
+
+fn coordinate_pair(
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+) -> Result<Option<(f64, f64)>, AppError> {
+    match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => {
+            validate_coordinates(latitude, longitude)?;
+            Ok(Some((latitude, longitude)))
+        }
+        (None, None) => Ok(None),
+        _ => Err(AppError::BadRequest(
+            "latitude and longitude must be given together".into(),
+        )),
+    }
+}
+
+const MAX_TITLE_LENGTH: usize = 120;
+const MAX_DESCRIPTION_LENGTH: usize = 4000;
+
+/// `value` without surrounding whitespace. Rejects it if nothing is left or it
+/// is longer than `max_length` characters.
+fn trimmed_text(field: &str, value: &str, max_length: usize) -> Result<String, AppError> {
+    let value = value.trim();
+
+    if value.is_empty() {
+        return Err(AppError::BadRequest(format!("{} must not be empty", field)));
+    }
+    if value.chars().count() > max_length {
+        return Err(AppError::BadRequest(format!(
+            "{} must be at most {} characters",
+            field, max_length
+        )));
+    }
+
+    Ok(value.to_string())
+}
+
+fn validate_quantity(quantity: i32) -> Result<(), AppError> {
+    if quantity < 1 {
+        return Err(AppError::BadRequest("Quantity must be at least 1".into()));
+    }
+    Ok(())
+}
+
+/// The largest value `products.price NUMERIC(12, 2)` can hold.
+const MAX_PRICE: f64 = 9_999_999_999.99;
+
+fn validate_price(price: f64) -> Result<(), AppError> {
+    // `f64::from_str` accepts "NaN" and "inf", which NUMERIC would store as-is.
+    if !price.is_finite() {
+        return Err(AppError::BadRequest("Invalid price format".into()));
+    }
+    if !(0.0..=MAX_PRICE).contains(&price) {
+        return Err(AppError::BadRequest(format!(
+            "Price must be between 0 and {}",
+            MAX_PRICE
+        )));
+    }
+
+    Ok(())
 }
 
-pub fn validate_phone_number(phone_number: &str) -> Result<(), actix_web::Error> {
+pub fn validate_phone_number(phone_number: &str) -> Result<(), AppError> {
     let phone_number_regex = Regex::new(r"^(\+380\d{9}|\d{10})$").unwrap();
 
     if !phone_number_regex.is_match(phone_number) {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid phone number format",
-        ))
+        Err(AppError::BadRequest("Invalid phone number format".into()))
     } else {
         Ok(())
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CreateProductResponse {
     pub product_id: i32,
 }
 
+/// Parses the create form, reporting every invalid field at once. A draft only
+/// needs a title; anything else also needs what `PublishFields` checks.
 fn parse_form_data(
     form: HashMap<String, String>,
-) -> Result<CreateProductRequest, actix_web::Error> {
-    let title = form
-        .get("title")
-        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing title"))?
-        .clone();
-    let description = form
-        .get("description")
-        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing description"))?
-        .clone();
-    let phone_number = form
-        .get("phone_number")
-        .ok_or_else(|| actix_web::error::ErrorBadRequest("Phone number is missing"))?
-        .clone();
-
-    validate_phone_number(&phone_number)?;
-
-    let price = form
-        .get("price")
-        .ok_or(actix_web::error::ErrorBadRequest("Missing price"))?
-        .parse::<f64>()
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid price format"))?;
-
-    let category_id = form
-        .get("category_id")
-        .ok_or(actix_web::error::ErrorBadRequest("Missing category"))?
-        .parse::<i32>()
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid price format"))?;
-
-    let delivery_option_ids = form
-        .get("delivery_option")
-        .map(|v| v.split(',').map(|s| s.parse::<i32>().unwrap()).collect())
-        .unwrap_or_else(|| vec![]);
-
-    let payment_option_ids = form
-        .get("payment_option")
-        .map(|v| v.split(',').map(|s| s.parse::<i32>().unwrap()).collect())
-        .unwrap_or_else(|| vec![]);
-
-    let brand = form.get("brand").cloned();
-
-    let condition = form
-        .get("condition")
-        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing condition"))?
-        .parse::<ProductCondition>()
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid condition"))?;
-
-    let color = form.get("color").cloned();
-    let shoe_size = form.get("shoe_size").cloned();
-    let clothing_size = form.get("clothing_size").cloned();
-    let gender = form.get("gender").cloned();
-    let material = form.get("material").cloned();
-
-    Ok(CreateProductRequest {
-        title,
+    photo_count: usize,
+) -> Result<CreateProductRequest, AppError> {
+    let mut errors = FieldErrors::default();
+
+    let draft = match form.get("status").map(String::as_str) {
+        None | Some("active") => Some(false),
+        Some("draft") => Some(true),
+        Some(_) => {
+            errors.add("status", "status must be either active or draft");
+            None
+        }
+    };
+
+    let title = match form.get("title") {
+        Some(title) => errors.check("title", trimmed_text("Title", title, MAX_TITLE_LENGTH)),
+        None => {
+            errors.add("title", "Missing title");
+            None
+        }
+    };
+    let description = form.get("description").and_then(|description| {
+        errors.check(
+            "description",
+            trimmed_text("Description", description, MAX_DESCRIPTION_LENGTH),
+        )
+    });
+
+    let phone_number = form.get("phone_number").and_then(|phone_number| {
+        errors
+            .check("phone_number", validate_phone_number(phone_number))
+            .map(|_| phone_number.clone())
+    });
+
+    let price = form.get("price").and_then(|price| {
+        let price = price
+            .parse::<f64>()
+            .map_err(|_| AppError::BadRequest("Invalid price format".into()))
+            .and_then(|price| validate_price(price).map(|_| price));
+        errors.check("price", price)
+    });
+
+    let currency = form
+        .get("currency")
+        .and_then(|currency| {
+            let currency = currency
+                .trim()
+                .parse::<Currency>()
+                .map_err(|_| AppError::BadRequest("Unsupported currency".into()));
+            errors.check("currency", currency)
+        })
+        .unwrap_or(Currency::Uah);
+
+    let quantity = match form.get("quantity") {
+        None => 1,
+        Some(quantity) => {
+            let quantity = quantity
+                .trim()
+                .parse::<i32>()
+                .map_err(|_| AppError::BadRequest("Invalid quantity".into()))
+                .and_then(|quantity| validate_quantity(quantity).map(|_| quantity));
+            errors.check("quantity", quantity).unwrap_or(1)
+        }
+    };
+
+    let category_id = form.get("category_id").and_then(|category_id| {
+        let category_id = category_id
+            .parse::<i32>()
+            .map_err(|_| AppError::BadRequest("Invalid category".into()));
+        errors.check("category_id", category_id)
+    });
+
+    let mut option_ids = |field: &str, kind: OptionKind| {
+        let Some(ids) = form.get(field) else {
+            return Vec::new();
+        };
+        let ids = ids
+            .split(',')
+            .map(|id| id.trim().parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| AppError::BadRequest(format!("Invalid {} option id", kind.label())));
+        errors.check(field, ids).unwrap_or_default()
+    };
+    let delivery_option_ids = option_ids("delivery_option", OptionKind::Delivery);
+    let payment_option_ids = option_ids("payment_option", OptionKind::Payment);
+
+    let brand = form.get("brand").and_then(|brand| normalize_brand(brand));
+
+    let condition = form.get("condition").and_then(|condition| {
+        let condition = condition
+            .parse::<ProductCondition>()
+            .map_err(|_| AppError::BadRequest("Invalid condition".into()));
+        errors.check("condition", condition)
+    });
+
+    let mut option = |field: &str, sets: &[OptionSet]| {
+        form.get(field).and_then(|value| {
+            errors
+                .check(field, validate_option(field, Some(value), sets))
+                .map(|_| value.clone())
+        })
+    };
+    let color = option("color", &[COLORS]);
+    let shoe_size = option("shoe_size", &[SHOE_SIZES]);
+    let clothing_size = option("clothing_size", &[CLOTHING_SIZES]);
+    let gender = option("gender", &[GENDERS]);
+    let material = option("material", MATERIAL_SETS);
+
+    let city = form
+        .get("city")
+        .map(|city| city.trim().to_string())
+        .filter(|city| !city.is_empty());
+
+    let mut coordinate = |field: &str| {
+        form.get(field).and_then(|value| {
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid {}", field)));
+            errors.check(field, value)
+        })
+    };
+    let latitude = coordinate("latitude");
+    let longitude = coordinate("longitude");
+    let field = if latitude.is_none() {
+        "latitude"
+    } else {
+        "longitude"
+    };
+    let (latitude, longitude) = errors
+        .check(field, coordinate_pair(latitude, longitude))
+        .flatten()
+        .unzip();
+
+    let data = CreateProductRequest {
+        draft: draft.unwrap_or(false),
+        title: title.unwrap_or_default(),
         description,
         category_id,
         brand,
         condition,
         price,
+        currency,
         phone_number,
+        quantity,
         delivery_option_ids,
         payment_option_ids,
         color,
@@ -234,115 +625,366 @@ fn parse_form_data(
         clothing_size,
         gender,
         material,
-    })
+        city,
+        latitude,
+        longitude,
+    };
+
+    if draft == Some(false) {
+        data.publish_fields(photo_count).check(&mut errors);
+    }
+    errors.into_result()?;
+
+    Ok(data)
+}
+
+/// `brand` with its whitespace collapsed, or `None` if nothing is left.
+fn normalize_brand(brand: &str) -> Option<String> {
+    let brand = brand.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!brand.is_empty()).then_some(brand)
+}
+
+/// The spelling most products already use for `brand`, ignoring case, so that
+/// "Nike" and "NIKE" end up as one brand. New brands are kept as given.
+async fn canonical_brand(
+    tx: &mut Transaction<'_, Postgres>,
+    brand: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let Some(brand) = brand else {
+        return Ok(None);
+    };
+
+    let existing: Option<String> = sqlx::query_scalar(
+        "SELECT brand FROM products
+         WHERE lower(brand) = lower($1)
+         GROUP BY brand
+         ORDER BY COUNT(*) DESC, brand
+         LIMIT 1",
+    )
+    .bind(&brand)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(Some(existing.unwrap_or(brand)))
 }
 
 async fn insert_product(
     tx: &mut sqlx::Transaction<'_, Postgres>,
     user_id: &Uuid,
     data: &CreateProductRequest,
-) -> Result<i32, actix_web::Error> {
+) -> Result<i32, AppError> {
     let rec = sqlx::query(
         "INSERT INTO products
         (user_id, title, description, category_id, brand, condition, price, phone_number,
-         color, shoe_size, clothing_size, gender, material)
+         color, shoe_size, clothing_size, gender, material, city, latitude, longitude, status,
+         quantity, currency)
         VALUES ($1, $2, $3, $4, $5, $6, $7, $8,
-                $9, $10, $11, $12, $13)
+                $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
         RETURNING id",
     )
     .bind(user_id)
     .bind(&data.title)
     .bind(&data.description)
-    .bind(&data.category_id)
+    .bind(data.category_id)
     .bind(&data.brand)
-    .bind(&data.condition.to_string())
-    .bind(&data.price)
+    .bind(data.condition.map(|condition| condition.to_string()))
+    .bind(data.price)
     .bind(&data.phone_number)
     .bind(&data.color)
     .bind(&data.shoe_size)
     .bind(&data.clothing_size)
     .bind(&data.gender)
     .bind(&data.material)
+    .bind(&data.city)
+    .bind(data.latitude)
+    .bind(data.longitude)
+    .bind(
+        if data.draft {
+            ProductStatus::Draft
+        } else {
+            ProductStatus::Active
+        }
+        .to_string(),
+    )
+    .bind(data.quantity)
+    .bind(data.currency.to_string())
     .fetch_one(&mut **tx)
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+    .map_err(AppError::internal)?;
 
-    Ok(rec
-        .try_get("id")
-        .map_err(actix_web::error::ErrorInternalServerError)?)
+    rec.try_get("id").map_err(AppError::internal)
 }
 
-async fn insert_product_options(
-    tx: &mut sqlx::Transaction<'_, Postgres>,
-    product_id: i32,
-    data: &CreateProductRequest,
-) -> Result<(), actix_web::Error> {
-    if !data.delivery_option_ids.is_empty() {
-        let mut builder = QueryBuilder::new(
-            "INSERT INTO product_delivery_options (product_id, delivery_option_id) ",
-        );
-        builder.push_values(
-            data.delivery_option_ids.iter().map(|id| (product_id, *id)),
-            |mut b, (pid, did)| {
-                b.push_bind(pid).push_bind(did);
-            },
-        );
-        builder
-            .build()
-            .execute(&mut **tx)
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+/// Delivery or payment options, each linked to products through their own table.
+#[derive(Clone, Copy)]
+enum OptionKind {
+    Delivery,
+    Payment,
+}
+
+impl OptionKind {
+    fn table(self) -> &'static str {
+        match self {
+            OptionKind::Delivery => "delivery_options",
+            OptionKind::Payment => "payment_options",
+        }
     }
 
-    if !data.payment_option_ids.is_empty() {
-        let mut builder = QueryBuilder::new(
-            "INSERT INTO product_payment_options (product_id, payment_option_id) ",
-        );
-        builder.push_values(
-            data.payment_option_ids.iter().map(|id| (product_id, *id)),
-            |mut b, (pid, pid_opt)| {
-                b.push_bind(pid).push_bind(pid_opt);
-            },
-        );
-        builder
-            .build()
-            .execute(&mut **tx)
+    fn link_table(self) -> &'static str {
+        match self {
+            OptionKind::Delivery => "product_delivery_options",
+            OptionKind::Payment => "product_payment_options",
+        }
+    }
+
+    fn link_column(self) -> &'static str {
+        match self {
+            OptionKind::Delivery => "delivery_option_id",
+            OptionKind::Payment => "payment_option_id",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            OptionKind::Delivery => "delivery option",
+            OptionKind::Payment => "payment option",
+        }
+    }
+}
+
+/// Fails with 400 naming the first id that isn't in the option table.
+async fn ensure_options_exist(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: OptionKind,
+    ids: &[i32],
+) -> Result<(), AppError> {
+    let unknown: Option<i32> = sqlx::query_scalar(&format!(
+        "SELECT id FROM UNNEST($1::int[]) AS requested (id)
+         WHERE NOT EXISTS (SELECT 1 FROM {} o WHERE o.id = requested.id)
+         LIMIT 1",
+        kind.table()
+    ))
+    .bind(ids)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    match unknown {
+        Some(id) => Err(AppError::BadRequest(format!(
+            "Unknown {} id {}",
+            kind.label(),
+            id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fails with 400 unless `category_id` is a category, so a bad id isn't
+/// reported as a foreign key violation.
+async fn ensure_category_exists(
+    tx: &mut Transaction<'_, Postgres>,
+    category_id: Option<i32>,
+) -> Result<(), AppError> {
+    let Some(category_id) = category_id else {
+        return Ok(());
+    };
+
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM categories WHERE category_id = $1)")
+            .bind(category_id)
+            .fetch_one(&mut **tx)
             .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            .map_err(AppError::internal)?;
+
+    if !exists {
+        return Err(AppError::BadRequest("Unknown category".into()));
+    }
+
+    Ok(())
+}
+
+async fn insert_product_options(
+    tx: &mut Transaction<'_, Postgres>,
+    product_id: i32,
+    kind: OptionKind,
+    ids: &[i32],
+) -> Result<(), AppError> {
+    ensure_options_exist(tx, kind, ids).await?;
+
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+
+    if ids.is_empty() {
+        return Ok(());
     }
 
+    let mut builder = QueryBuilder::new(format!(
+        "INSERT INTO {} (product_id, {}) ",
+        kind.link_table(),
+        kind.link_column()
+    ));
+    builder.push_values(ids, |mut b, id| {
+        b.push_bind(product_id).push_bind(id);
+    });
+    builder
+        .build()
+        .execute(&mut **tx)
+        .await
+        .map_err(AppError::internal)?;
+
     Ok(())
 }
 
+/// Replaces the product's options of `kind` with `ids`; an empty list clears them.
+async fn replace_product_options(
+    tx: &mut Transaction<'_, Postgres>,
+    product_id: i32,
+    kind: OptionKind,
+    ids: &[i32],
+) -> Result<(), AppError> {
+    sqlx::query(&format!(
+        "DELETE FROM {} WHERE product_id = $1",
+        kind.link_table()
+    ))
+    .bind(product_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    insert_product_options(tx, product_id, kind, ids).await
+}
+
 async fn insert_product_photo(
     tx: &mut Transaction<'_, Postgres>,
     product_id: i32,
     photo_url: &str,
     position: i32,
-) -> Result<(), actix_web::Error> {
+) -> Result<(), AppError> {
     sqlx::query("INSERT INTO product_images (product_id, url, position) VALUES ($1, $2, $3)")
         .bind(product_id)
         .bind(photo_url)
         .bind(position)
         .execute(&mut **tx)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
     Ok(())
 }
 
-#[post("/create")]
-pub async fn create(
-    user: AuthenticatedUser,
-    mut payload: Multipart,
-    db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
-    let user_id = &user.0.sub;
+/// Uploads `photos` and records them with positions counting up from `first_position`.
+async fn store_photos(
+    tx: &mut Transaction<'_, Postgres>,
+    product_id: i32,
+    photos: Vec<EncodedImage>,
+    first_position: i32,
+) -> Result<(), AppError> {
+    for (index, photo) in photos.into_iter().enumerate() {
+        let (extension, content_type) = (photo.extension(), photo.content_type());
+        let photo_url = upload_to_s3(photo.bytes, extension, content_type).await?;
+
+        insert_product_photo(tx, product_id, &photo_url, first_position + index as i32).await?;
+    }
+
+    Ok(())
+}
+
+/// Uploads `video` as the product's only video, returning the url of the one
+/// it replaces.
+async fn store_video(
+    tx: &mut Transaction<'_, Postgres>,
+    product_id: i32,
+    video: EncodedVideo,
+) -> Result<(String, Option<String>), AppError> {
+    let (extension, content_type) = (video.format.extension(), video.format.content_type());
+    let url = upload_to_s3(video.bytes, extension, content_type).await?;
+
+    let replaced: Option<String> =
+        sqlx::query_scalar("DELETE FROM product_videos WHERE product_id = $1 RETURNING url")
+            .bind(product_id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    sqlx::query("INSERT INTO product_videos (product_id, url) VALUES ($1, $2)")
+        .bind(product_id)
+        .bind(&url)
+        .execute(&mut **tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    Ok((url, replaced))
+}
+
+/// Longest text field of a product form. The description, the longest real
+/// field, is at most 4000 characters.
+const MAX_FORM_FIELD_BYTES: usize = 16 * 1024;
+
+/// Counts the bytes read from a multipart request against `MAX_REQUEST_UPLOAD_BYTES`.
+struct UploadBudget {
+    remaining: usize,
+}
+
+impl UploadBudget {
+    fn new() -> Self {
+        Self {
+            remaining: *MAX_REQUEST_UPLOAD_BYTES,
+        }
+    }
+
+    fn spend(&mut self, bytes: usize) -> Result<(), AppError> {
+        self.remaining = self.remaining.checked_sub(bytes).ok_or_else(|| {
+            AppError::PayloadTooLarge(format!(
+                "The request can be at most {} bytes",
+                *MAX_REQUEST_UPLOAD_BYTES
+            ))
+        })?;
+        Ok(())
+    }
+}
+
+/// Reads one multipart field, failing with 413 once it is over `limit` bytes.
+/// `what` names the field in that error.
+async fn read_field(
+    field: &mut Field,
+    budget: &mut UploadBudget,
+    limit: usize,
+    what: &str,
+) -> Result<Vec<u8>, AppError> {
+    let mut bytes = Vec::new();
+
+    while let Some(chunk) = field.next().await {
+        let data = chunk.map_err(|e| AppError::BadRequest(e.to_string()))?;
+        budget.spend(data.len())?;
+        bytes.extend_from_slice(&data);
+        if bytes.len() > limit {
+            return Err(AppError::PayloadTooLarge(format!(
+                "{} can be at most {} bytes",
+                what, limit
+            )));
+        }
+    }
+
+    Ok(bytes)
+}
 
+/// A multipart product form: text fields, `photos` files and an optional `video`.
+struct ProductForm {
+    fields: HashMap<String, String>,
+    photos: Vec<EncodedImage>,
+    video: Option<EncodedVideo>,
+}
+
+/// Reads a multipart product form. Stops reading with 413 as soon as a file, a
+/// text field or the request as a whole is over its limit.
+async fn read_product_form(mut payload: Multipart) -> Result<ProductForm, AppError> {
     let mut form_data = HashMap::new();
     let mut photos = Vec::new();
+    let mut video = None;
+    let mut budget = UploadBudget::new();
 
     while let Some(field) = payload.next().await {
-        let mut field = field.map_err(actix_web::error::ErrorInternalServerError)?;
+        let mut field = field.map_err(|e| AppError::BadRequest(e.to_string()))?;
         let name = field
             .content_disposition()
             .unwrap()
@@ -358,112 +1000,372 @@ pub async fn create(
                 .map(sanitize_filename::sanitize)
                 .unwrap_or_else(|| "upload.jpg".to_string());
 
-            let mut bytes = Vec::new();
-            while let Some(chunk) = field.next().await {
-                let data = chunk.map_err(actix_web::error::ErrorInternalServerError)?;
-                bytes.extend_from_slice(&data);
-                if bytes.len() > MAX_FILE_SIZE {
-                    return Err(actix_web::error::ErrorBadRequest("File too large"));
-                }
-            }
+            let bytes =
+                read_field(&mut field, &mut budget, *MAX_UPLOAD_BYTES, "Each photo").await?;
 
             let mime = from_path(&filename).first_or_octet_stream();
-            if !matches!(
-                mime.essence_str(),
-                "image/jpeg" | "image/png" | "image/jpg" | "image/webp"
-            ) {
-                return Err(actix_web::error::ErrorBadRequest("Invalid file type"));
+            if !is_allowed_image_type(mime.essence_str()) {
+                return Err(AppError::BadRequest("Invalid file type".into()));
             }
 
-            photos.push((bytes, filename));
-        } else {
-            let mut value = Vec::new();
-            while let Some(chunk) = field.next().await {
-                value.extend_from_slice(&chunk?);
+            validate_dimensions(&bytes)?;
+
+            let photo = web::block(move || strip_metadata(&bytes))
+                .await
+                .map_err(AppError::internal)??;
+
+            photos.push(photo);
+        } else if name == "video" {
+            if video.is_some() {
+                return Err(AppError::BadRequest(
+                    "Only one video can be uploaded".into(),
+                ));
             }
+
+            let bytes = read_field(&mut field, &mut budget, *MAX_VIDEO_BYTES, "The video").await?;
+            video = Some(EncodedVideo::new(bytes)?);
+        } else {
+            let value = read_field(&mut field, &mut budget, MAX_FORM_FIELD_BYTES, &name).await?;
             form_data.insert(name, String::from_utf8_lossy(&value).to_string());
         }
     }
 
-    let data = parse_form_data(form_data)?;
+    Ok(ProductForm {
+        fields: form_data,
+        photos,
+        video,
+    })
+}
 
-    if photos.is_empty() {
-        return Err(actix_web::error::ErrorBadRequest(
-            "At least one photo is required",
-        ));
-    }
+/// Multipart form accepted by `create`. Only used for the API docs.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct CreateProductForm {
+    /// `draft` saves the product unpublished, with every field but `title` optional.
+    #[schema(example = "active")]
+    status: Option<String>,
+    #[schema(max_length = 120)]
+    title: String,
+    #[schema(max_length = 4000)]
+    description: String,
+    phone_number: String,
+    #[schema(example = "99.99")]
+    price: String,
+    /// Currency of `price`, UAH unless set.
+    currency: Option<Currency>,
+    /// Number of items for sale, 1 unless set.
+    quantity: Option<i32>,
+    category_id: i32,
+    /// Comma-separated delivery option ids, e.g. `1,2`.
+    delivery_option: Option<String>,
+    /// Comma-separated payment option ids, e.g. `1`.
+    payment_option: Option<String>,
+    brand: Option<String>,
+    condition: ProductCondition,
+    color: Option<String>,
+    shoe_size: Option<String>,
+    clothing_size: Option<String>,
+    gender: Option<String>,
+    material: Option<String>,
+    city: Option<String>,
+    /// Must be sent together with `longitude`.
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// One or more images, jpeg, png or webp unless `ALLOWED_IMAGE_TYPES` says otherwise.
+    #[schema(value_type = Vec<String>, format = Binary)]
+    photos: Vec<Vec<u8>>,
+    /// An mp4 or webm file, 20 MB at most unless configured otherwise.
+    #[schema(value_type = Option<String>, format = Binary)]
+    video: Option<Vec<u8>>,
+}
 
-    let mut tx = db_pool
-        .begin()
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+const IDEMPOTENCY_KEY_TTL_HOURS: i32 = 24;
 
-    let product_id = insert_product(&mut tx, user_id, &data).await?;
+fn idempotency_key(req: &HttpRequest) -> Result<Option<String>, AppError> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
 
-    for (index, (photo_bytes, photo_filename)) in photos.into_iter().enumerate() {
-        let photo_url = upload_to_s3(
-            AWS_MARKETPLACE_BUCKET.as_str(),
-            photo_bytes,
-            &photo_filename,
-        )
-        .await?;
+    match value.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH => {
+            Ok(Some(key.to_string()))
+        }
+        _ => Err(AppError::BadRequest(
+            "Invalid Idempotency-Key header".into(),
+        )),
+    }
+}
+
+/// Claims `key` for the user. Returns `None` when the key is new, or the product
+/// created by the earlier request that used it. A concurrent request with the
+/// same key waits here until the first one commits or rolls back.
+async fn claim_idempotency_key(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    key: &str,
+) -> Result<Option<i32>, AppError> {
+    sqlx::query(
+        "DELETE FROM idempotency_keys
+         WHERE user_id = $1 AND created_at < NOW() - make_interval(hours => $2)",
+    )
+    .bind(user_id)
+    .bind(IDEMPOTENCY_KEY_TTL_HOURS)
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, key) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(key)
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::internal)?;
 
-        insert_product_photo(&mut tx, product_id, &photo_url, index as i32).await?;
+    if claimed.rows_affected() == 1 {
+        return Ok(None);
     }
 
-    insert_product_options(&mut tx, product_id, &data).await?;
+    let product_id: Option<i32> = sqlx::query_scalar(
+        "SELECT product_id FROM idempotency_keys WHERE user_id = $1 AND key = $2",
+    )
+    .bind(user_id)
+    .bind(key)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(AppError::internal)?
+    .flatten();
+
+    // The claiming request commits the key together with its product or rolls
+    // both back, so a key without a product is not expected here.
+    product_id
+        .map(Some)
+        .ok_or_else(|| AppError::Conflict("Idempotency-Key is already in use".into()))
+}
+
+fn created_response(product_id: i32) -> HttpResponse {
+    HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/api/v1/products/{}", product_id)))
+        .json(CreateProductResponse { product_id })
+}
 
-    tx.commit()
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(
+        ("Idempotency-Key" = Option<String>, Header,
+            description = "Repeating a create with the same key within 24 hours returns the product made by the first request instead of a new one")
+    ),
+    request_body(content = CreateProductForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Product created", body = CreateProductResponse),
+        (status = 400, description = "Invalid form data", body = ErrorEnvelope),
+        (status = 401, description = "Missing or invalid token", body = ErrorEnvelope),
+        (status = 409, description = "Idempotency-Key is already in use", body = ErrorEnvelope),
+        (status = 413, description = "A photo, a text field or the whole form is too large", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/create")]
+pub async fn create(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    payload: Multipart,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let user_id = &user.0.sub;
+    let idempotency_key = idempotency_key(&req)?;
+
+    let form = read_product_form(payload).await?;
+
+    let mut data = parse_form_data(form.fields, form.photos.len())?;
+
+    let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+    if let Some(key) = &idempotency_key
+        && let Some(product_id) = claim_idempotency_key(&mut tx, user_id, key).await?
+    {
+        return Ok(created_response(product_id));
+    }
+
+    ensure_category_exists(&mut tx, data.category_id).await?;
+    data.brand = canonical_brand(&mut tx, data.brand.take()).await?;
+    let product_id = insert_product(&mut tx, user_id, &data).await?;
 
-    Ok(HttpResponse::Ok().body("Product created"))
+    store_photos(&mut tx, product_id, form.photos, 0).await?;
+    if let Some(video) = form.video {
+        store_video(&mut tx, product_id, video).await?;
+    }
+
+    insert_product_options(
+        &mut tx,
+        product_id,
+        OptionKind::Delivery,
+        &data.delivery_option_ids,
+    )
+    .await?;
+    insert_product_options(
+        &mut tx,
+        product_id,
+        OptionKind::Payment,
+        &data.payment_option_ids,
+    )
+    .await?;
+
+    if let Some(key) = &idempotency_key {
+        sqlx::query("UPDATE idempotency_keys SET product_id = $1 WHERE user_id = $2 AND key = $3")
+            .bind(product_id)
+            .bind(user_id)
+            .bind(key)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+    }
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(created_response(product_id))
 }
 
-#[derive(Deserialize)]
+/// Filters of a product listing. Unknown parameters are rejected so that a
+/// misspelled filter fails loudly instead of being ignored.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
 pub struct ProductQuery {
-    category: Option<String>,
+    category: Option<i32>,
+    /// Keyset cursor: the last id of the previous page. Rejected when results
+    /// are ranked or sorted by distance.
     last_seen_id: Option<i64>,
+    /// Page size, 20 unless set. Values above 100 are treated as 100.
     limit: Option<i64>,
+    /// Drafts are included when this is the caller's own id.
     user_id: Option<Uuid>,
     search: Option<String>,
+    /// How `search` is matched; `substring` unless set.
+    search_mode: Option<SearchMode>,
+    /// Rows to skip. Use instead of `last_seen_id` when results are ranked or
+    /// sorted by distance.
+    offset: Option<i64>,
+    include_sold: Option<bool>,
+    /// Comma-separated list, e.g. `red,blue`.
+    color: Option<String>,
+    /// Comma-separated list, e.g. `40,41`.
+    shoe_size: Option<String>,
+    /// Comma-separated list, e.g. `M,L`.
+    clothing_size: Option<String>,
+    gender: Option<String>,
+    material: Option<String>,
+    /// Case-insensitive city name.
+    city: Option<String>,
+    /// Comma-separated list of brands, matched ignoring case.
+    brand: Option<String>,
+    /// Searches around this point when given together with `lon`; nearest first.
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// Only products within this many kilometres of `lat`/`lon`.
+    radius_km: Option<f64>,
+    /// Leaves out products with nothing left in stock.
+    in_stock: Option<bool>,
+    /// Only products priced in this currency. Required by `min_price` and `max_price`.
+    currency: Option<Currency>,
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    condition: Option<ProductCondition>,
+    /// Only products of users who have taken on the seller role.
+    sellers_only: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Case-insensitive substring match on title and description.
+    Substring,
+    /// Full-text match ordered by relevance, title matches ranking higher.
+    Fulltext,
+}
+
+/// Splits a comma-separated filter into values, rejecting any that are not in `sets`.
+fn filter_values(field: &str, raw: &str, sets: &[OptionSet]) -> Result<Vec<String>, AppError> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| validate_option(field, Some(value), sets).map(|_| value.to_string()))
+        .collect()
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct Photo {
     id: i32,
     url: String,
 }
 
-#[derive(FromRow, Serialize)]
+#[derive(FromRow, Serialize, ToSchema)]
 pub struct Product {
     id: i32,
     title: String,
-    category_id: i32,
-    description: String,
+    category_id: Option<i32>,
+    description: Option<String>,
     brand: Option<String>,
-    condition: String,
-    price: BigDecimal,
-    phone_number: String,
-    created_at: NaiveDateTime,
+    condition: Option<String>,
+    #[schema(value_type = Option<String>, example = "99.99")]
+    price: Option<BigDecimal>,
+    #[schema(value_type = Currency)]
+    currency: String,
+    phone_number: Option<String>,
+    created_at: DateTime<Utc>,
+    /// Last change to the product or its photos.
+    updated_at: DateTime<Utc>,
     user_id: Uuid,
+    /// Seller's first and last name, as shown on their public profile.
+    seller_name: String,
     color: Option<String>,
     shoe_size: Option<String>,
     clothing_size: Option<String>,
     gender: Option<String>,
     material: Option<String>,
+    city: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// Items left; marking the product sold takes one off.
+    quantity: i32,
+    status: String,
+    #[schema(value_type = Vec<Photo>)]
     photos: Json<Vec<Photo>>,
+    video_url: Option<String>,
+    /// Delivery methods the seller offers.
+    #[serde(rename = "delivery_options")]
+    #[sqlx(rename = "delivery_options")]
+    #[schema(value_type = Vec<DeliveryOptions>)]
+    delivery: Json<Vec<DeliveryOptions>>,
+    /// Payment methods the seller accepts.
+    #[serde(rename = "payment_options")]
+    #[sqlx(rename = "payment_options")]
+    #[schema(value_type = Vec<PaymentOptions>)]
+    payment: Json<Vec<PaymentOptions>>,
 }
 
-#[get("")]
-pub async fn get_products(
-    pool: web::Data<PgPool>,
-    query: web::Query<ProductQuery>,
-) -> Result<HttpResponse, actix_web::Error> {
-    let limit = query.limit.unwrap_or(20);
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Pushes the haversine distance in kilometres between the product and the point.
+fn push_distance(qb: &mut BoundSql, lat: f64, lon: f64) {
+    qb.push("(2 * ");
+    qb.push_bind(EARTH_RADIUS_KM);
+    qb.push(" * asin(sqrt(power(sin(radians(p.latitude - ");
+    qb.push_bind(lat);
+    qb.push(") / 2), 2) + cos(radians(");
+    qb.push_bind(lat);
+    qb.push(")) * cos(radians(p.latitude)) * power(sin(radians(p.longitude - ");
+    qb.push_bind(lon);
+    qb.push(") / 2), 2))))");
+}
 
-    let mut qb = QueryBuilder::new(
-        r#"
+/// Columns and photos of a product. Callers append their own WHERE clause and
+/// must finish with `GROUP BY p.id`.
+const PRODUCT_SELECT: &str = r#"
     SELECT
         p.id,
         p.title,
@@ -472,825 +1374,2317 @@ pub async fn get_products(
         p.brand,
         p.condition,
         p.price,
+        p.currency,
         p.phone_number,
         p.created_at,
+        p.updated_at,
         p.user_id,
+        (
+            SELECT concat_ws(' ', u.first_name, u.last_name)
+            FROM users u
+            WHERE u.id = p.user_id
+        ) AS seller_name,
         p.color,
         p.shoe_size,
         p.clothing_size,
         p.gender,
         p.material,
+        p.city,
+        p.latitude,
+        p.longitude,
+        p.status,
+        p.quantity,
         COALESCE(
             json_agg(
                 json_build_object('id', ph.id, 'url', ph.url)
+                ORDER BY ph.position
             ) FILTER (WHERE ph.id IS NOT NULL),
             '[]'
-        )::json AS photos
+        )::json AS photos,
+        (SELECT v.url FROM product_videos v WHERE v.product_id = p.id) AS video_url,
+        (
+            SELECT COALESCE(json_agg(json_build_object('id', d.id, 'name', d.name) ORDER BY d.id), '[]')
+            FROM product_delivery_options pd
+            JOIN delivery_options d ON d.id = pd.delivery_option_id
+            WHERE pd.product_id = p.id
+        )::json AS delivery_options,
+        (
+            SELECT COALESCE(json_agg(json_build_object('id', po.id, 'name', po.name) ORDER BY po.id), '[]')
+            FROM product_payment_options pp
+            JOIN payment_options po ON po.id = pp.payment_option_id
+            WHERE pp.product_id = p.id
+        )::json AS payment_options
     FROM products p
     LEFT JOIN product_images ph ON ph.product_id = p.id
-    WHERE 1=1
-"#,
-    );
+"#;
+
+/// Whose products a listing query may return, on top of the query's own filters.
+pub enum ProductScope {
+    /// The public catalogue. Drafts are only included when `viewer` filters by
+    /// their own `user_id`.
+    Public { viewer: Option<Uuid> },
+    /// Every product of the owner, whatever its status.
+    Owner(Uuid),
+}
 
-    if let Some(category_id) = &query.category {
-        qb.push(" AND p.category_id = ");
-        qb.push_bind(category_id);
+const DEFAULT_PRODUCTS_LIMIT: i64 = 20;
+const MAX_PRODUCTS_LIMIT: i64 = 100;
+
+/// A value bound to one `$n` placeholder of the listing query.
+#[derive(Debug, Clone, PartialEq)]
+enum Bind {
+    Text(String),
+    TextArray(Vec<String>),
+    Int(i64),
+    Float(f64),
+    Uuid(Uuid),
+}
+
+impl From<&str> for Bind {
+    fn from(value: &str) -> Self {
+        Bind::Text(value.to_string())
+    }
+}
+
+impl From<String> for Bind {
+    fn from(value: String) -> Self {
+        Bind::Text(value)
+    }
+}
+
+impl From<Vec<String>> for Bind {
+    fn from(value: Vec<String>) -> Self {
+        Bind::TextArray(value)
+    }
+}
+
+impl From<i64> for Bind {
+    fn from(value: i64) -> Self {
+        Bind::Int(value)
+    }
+}
+
+impl From<f64> for Bind {
+    fn from(value: f64) -> Self {
+        Bind::Float(value)
+    }
+}
+
+impl From<Uuid> for Bind {
+    fn from(value: Uuid) -> Self {
+        Bind::Uuid(value)
+    }
+}
+
+/// SQL text and the values of its placeholders, in order. Built without sqlx so
+/// the listing query can be checked without a database.
+#[derive(Debug, Default)]
+struct BoundSql {
+    sql: String,
+    binds: Vec<Bind>,
+}
+
+impl BoundSql {
+    fn new(sql: &str) -> Self {
+        Self {
+            sql: sql.to_string(),
+            binds: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, sql: &str) {
+        self.sql.push_str(sql);
+    }
+
+    fn push_bind(&mut self, value: impl Into<Bind>) {
+        self.binds.push(value.into());
+        self.sql.push_str(&format!("${}", self.binds.len()));
     }
 
-    if let Some(user_id) = &query.user_id {
-        qb.push(" AND p.user_id = ");
-        qb.push_bind(user_id);
+    fn arguments(&self) -> Result<PgArguments, AppError> {
+        let mut arguments = PgArguments::default();
+
+        for bind in &self.binds {
+            match bind {
+                Bind::Text(value) => arguments.add(value),
+                Bind::TextArray(value) => arguments.add(value),
+                Bind::Int(value) => arguments.add(value),
+                Bind::Float(value) => arguments.add(value),
+                Bind::Uuid(value) => arguments.add(value),
+            }
+            .map_err(AppError::internal)?;
+        }
+
+        Ok(arguments)
+    }
+}
+
+/// Builds the listing query for `query` within `scope`.
+fn products_query(query: &ProductQuery, scope: ProductScope) -> Result<BoundSql, AppError> {
+    let limit = match query.limit {
+        None => DEFAULT_PRODUCTS_LIMIT,
+        Some(limit) if limit <= 0 => {
+            return Err(AppError::BadRequest("limit must be positive".into()));
+        }
+        Some(limit) => limit.min(MAX_PRODUCTS_LIMIT),
+    };
+
+    if query.offset.is_some_and(|offset| offset < 0) {
+        return Err(AppError::BadRequest("offset can't be negative".into()));
     }
 
+    let mut qb = BoundSql::new(PRODUCT_SELECT);
+    let point = push_filters(&mut qb, query, scope)?;
+    let ranked = query.search.is_some() && query.search_mode == Some(SearchMode::Fulltext);
+
     if let Some(last_seen_id) = query.last_seen_id {
+        // Ids only page correctly when results are ordered by id.
+        if ranked || point.is_some() {
+            return Err(AppError::BadRequest(
+                "last_seen_id can't be combined with fulltext search or a lat/lon sort, use offset"
+                    .into(),
+            ));
+        }
         qb.push(" AND p.id < ");
         qb.push_bind(last_seen_id);
     }
 
-    if let Some(search) = &query.search {
-        qb.push(" AND (p.title ILIKE ");
-        qb.push_bind(format!("%{}%", search));
-        qb.push(" OR p.description ILIKE ");
-        qb.push_bind(format!("%{}%", search));
-        qb.push(")");
+    qb.push(" GROUP BY p.id ORDER BY ");
+
+    match &query.search {
+        Some(search) if ranked => {
+            qb.push("ts_rank(p.search_vector, plainto_tsquery('simple', ");
+            qb.push_bind(search.as_str());
+            qb.push(")) DESC, p.id DESC");
+        }
+        _ => {
+            if let Some((lat, lon)) = point {
+                push_distance(&mut qb, lat, lon);
+                qb.push(" ASC NULLS LAST, ");
+            }
+            qb.push("p.id DESC");
+        }
     }
 
-    qb.push(" GROUP BY p.id ORDER BY p.id DESC LIMIT ");
+    qb.push(" LIMIT ");
     qb.push_bind(limit);
 
-    let rows = qb
-        .build_query_as::<Product>()
-        .fetch_all(pool.get_ref())
+    if let Some(offset) = query.offset {
+        qb.push(" OFFSET ");
+        qb.push_bind(offset);
+    }
+
+    Ok(qb)
+}
+
+/// Builds the query counting every product `products_query` could page through.
+fn count_query(query: &ProductQuery, scope: ProductScope) -> Result<BoundSql, AppError> {
+    let mut qb = BoundSql::new("SELECT COUNT(*) FROM products p");
+    push_filters(&mut qb, query, scope)?;
+
+    Ok(qb)
+}
+
+/// Appends the WHERE clause shared by listings and counts, returning the point
+/// to measure distances from, if any.
+fn push_filters(
+    qb: &mut BoundSql,
+    query: &ProductQuery,
+    scope: ProductScope,
+) -> Result<Option<(f64, f64)>, AppError> {
+    qb.push(" WHERE 1=1");
+
+    match scope {
+        ProductScope::Public { viewer } => {
+            // Sold items stay reachable on request and drafts are only shown to
+            // their owner; archived ones are never listed.
+            let mut statuses = vec![ProductStatus::Active.to_string()];
+            if query.include_sold.unwrap_or(false) {
+                statuses.push(ProductStatus::Sold.to_string());
+            }
+            if viewer.is_some() && viewer == query.user_id {
+                statuses.push(ProductStatus::Draft.to_string());
+            }
+
+            qb.push(" AND p.status = ANY(");
+            qb.push_bind(statuses);
+            qb.push(")");
+
+            if let Some(user_id) = query.user_id {
+                qb.push(" AND p.user_id = ");
+                qb.push_bind(user_id);
+            }
+        }
+        ProductScope::Owner(owner) => {
+            // Both are implied here; accepting them would suggest they filter.
+            if query.user_id.is_some() || query.include_sold.is_some() {
+                return Err(AppError::BadRequest(
+                    "user_id and include_sold can't be used when listing your own products".into(),
+                ));
+            }
+            qb.push(" AND p.user_id = ");
+            qb.push_bind(owner);
+        }
+    }
+
+    if let Some(category_id) = query.category {
+        qb.push(" AND p.category_id = ");
+        qb.push_bind(i64::from(category_id));
+    }
+
+    if let Some(condition) = query.condition {
+        qb.push(" AND p.condition = ");
+        qb.push_bind(condition.to_string());
+    }
+
+    if let Some(currency) = query.currency {
+        qb.push(" AND p.currency = ");
+        qb.push_bind(currency.to_string());
+    }
+
+    // Prices in different currencies can't be compared, so a range needs one.
+    if query.min_price.is_some() || query.max_price.is_some() {
+        if query.currency.is_none() {
+            return Err(AppError::BadRequest(
+                "min_price and max_price require currency".into(),
+            ));
+        }
+        if let (Some(min_price), Some(max_price)) = (query.min_price, query.max_price)
+            && min_price > max_price
+        {
+            return Err(AppError::BadRequest(
+                "min_price can't be greater than max_price".into(),
+            ));
+        }
+    }
+
+    if let Some(min_price) = query.min_price {
+        qb.push(" AND p.price >= ");
+        qb.push_bind(min_price);
+    }
+
+    if let Some(max_price) = query.max_price {
+        qb.push(" AND p.price <= ");
+        qb.push_bind(max_price);
+    }
+
+    if query.in_stock.unwrap_or(false) {
+        qb.push(" AND p.quantity > 0");
+    }
+
+    if query.sellers_only.unwrap_or(false) {
+        qb.push(" AND EXISTS (SELECT 1 FROM sellers s WHERE s.user_id = p.user_id)");
+    }
+
+    if let Some(color) = &query.color {
+        qb.push(" AND p.color = ANY(");
+        qb.push_bind(filter_values("color", color, &[COLORS])?);
+        qb.push(")");
+    }
+
+    if let Some(shoe_size) = &query.shoe_size {
+        qb.push(" AND p.shoe_size = ANY(");
+        qb.push_bind(filter_values("shoe_size", shoe_size, &[SHOE_SIZES])?);
+        qb.push(")");
+    }
+
+    if let Some(clothing_size) = &query.clothing_size {
+        qb.push(" AND p.clothing_size = ANY(");
+        qb.push_bind(filter_values(
+            "clothing_size",
+            clothing_size,
+            &[CLOTHING_SIZES],
+        )?);
+        qb.push(")");
+    }
+
+    if let Some(gender) = &query.gender {
+        validate_option("gender", Some(gender), &[GENDERS])?;
+        qb.push(" AND p.gender = ");
+        qb.push_bind(gender.as_str());
+    }
+
+    if let Some(material) = &query.material {
+        validate_option("material", Some(material), MATERIAL_SETS)?;
+        qb.push(" AND p.material = ");
+        qb.push_bind(material.as_str());
+    }
+
+    if let Some(city) = &query.city {
+        qb.push(" AND lower(p.city) = lower(");
+        qb.push_bind(city.trim());
+        qb.push(")");
+    }
+
+    if let Some(brands) = &query.brand {
+        let brands: Vec<String> = brands
+            .split(',')
+            .filter_map(normalize_brand)
+            .map(|brand| brand.to_lowercase())
+            .collect();
+
+        if !brands.is_empty() {
+            qb.push(" AND lower(p.brand) = ANY(");
+            qb.push_bind(brands);
+            qb.push(")");
+        }
+    }
+
+    let point = match (query.lat, query.lon) {
+        (Some(lat), Some(lon)) => {
+            validate_coordinates(lat, lon)?;
+            Some((lat, lon))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(AppError::BadRequest(
+                "lat and lon must be given together".into(),
+            ));
+        }
+    };
+
+    if let Some(radius_km) = query.radius_km {
+        let Some((lat, lon)) = point else {
+            return Err(AppError::BadRequest(
+                "radius_km requires lat and lon".into(),
+            ));
+        };
+
+        if radius_km <= 0.0 {
+            return Err(AppError::BadRequest("radius_km must be positive".into()));
+        }
+
+        qb.push(" AND p.latitude IS NOT NULL AND ");
+        push_distance(qb, lat, lon);
+        qb.push(" <= ");
+        qb.push_bind(radius_km);
+    }
+
+    if let Some(search) = &query.search {
+        match query.search_mode.unwrap_or(SearchMode::Substring) {
+            SearchMode::Substring => {
+                qb.push(" AND (p.title ILIKE ");
+                qb.push_bind(format!("%{}%", escape_like(search)));
+                qb.push(" OR p.description ILIKE ");
+                qb.push_bind(format!("%{}%", escape_like(search)));
+                qb.push(")");
+            }
+            SearchMode::Fulltext => {
+                qb.push(" AND p.search_vector @@ plainto_tsquery('simple', ");
+                qb.push_bind(search.as_str());
+                qb.push(")");
+            }
+        }
+    }
+
+    Ok(point)
+}
+
+/// Responds with `body` as JSON tagged with a hash of it, or with 304 Not
+/// Modified when the client's `If-None-Match` already names that hash.
+///
+/// The body depends on who is asking (owners see their drafts), so shared
+/// caches must not store it and have to revalidate per token.
+fn json_with_etag(req: &HttpRequest, body: &impl Serialize) -> Result<HttpResponse, AppError> {
+    let body = serde_json::to_vec(body).map_err(AppError::internal)?;
+    let etag = EntityTag::new_strong(hex::encode(&Sha256::digest(&body)[..16]));
+
+    let unchanged = match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        Err(_) => false,
+    };
+
+    let mut response = if unchanged {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header(header::ETag(etag))
+        .insert_header((header::VARY, "Authorization"))
+        .insert_header(CacheControl(vec![
+            CacheDirective::Private,
+            CacheDirective::NoCache,
+        ]));
+
+    if unchanged {
+        return Ok(response.finish());
+    }
+
+    Ok(response.content_type(ContentType::json()).body(body))
+}
+
+pub async fn fetch_products(
+    pool: &PgPool,
+    query: &ProductQuery,
+    scope: ProductScope,
+) -> Result<Vec<Product>, AppError> {
+    let query = products_query(query, scope)?;
+
+    sqlx::query_as_with::<_, Product, _>(&query.sql, query.arguments()?)
+        .fetch_all(pool)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(ProductQuery),
+    responses(
+        (status = 200, description = "Products, newest first", body = Vec<Product>),
+        (status = 304, description = "Unchanged since the `If-None-Match` ETag"),
+        (status = 400, description = "Invalid filter value", body = ErrorEnvelope)
+    ),
+    security((), ("bearer_auth" = []))
+)]
+#[get("")]
+pub async fn get_products(
+    http_req: HttpRequest,
+    user: Option<AuthenticatedUser>,
+    pool: web::Data<PgPool>,
+    query: web::Query<ProductQuery>,
+) -> Result<HttpResponse, AppError> {
+    let scope = ProductScope::Public {
+        viewer: user.map(|user| user.0.sub),
+    };
+    let rows = fetch_products(pool.get_ref(), &query, scope).await?;
 
-    Ok(HttpResponse::Ok().json(rows))
+    json_with_etag(&http_req, &rows)
 }
 
-#[derive(Serialize)]
-pub struct OptionValue {
-    pub value: String,
-    pub label: String,
+#[derive(Serialize, ToSchema)]
+pub struct ProductCount {
+    count: i64,
 }
 
-#[get("/options/colors")]
-async fn get_colors() -> impl Responder {
-    let data = vec![
-        OptionValue {
-            value: "red".into(),
-            label: "Червоний".into(),
-        },
-        OptionValue {
-            value: "pink".into(),
-            label: "Рожевий".into(),
-        },
-        OptionValue {
-            value: "blue".into(),
-            label: "Синій".into(),
-        },
-        OptionValue {
-            value: "yellow".into(),
-            label: "Жовтий".into(),
-        },
-        OptionValue {
-            value: "grey".into(),
-            label: "Сірий".into(),
-        },
-        OptionValue {
-            value: "black".into(),
-            label: "Чорний".into(),
-        },
-        OptionValue {
-            value: "white".into(),
-            label: "Білий".into(),
-        },
-        OptionValue {
-            value: "other".into(),
-            label: "Інший".into(),
-        },
-    ];
-    HttpResponse::Ok().json(data)
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(ProductQuery),
+    responses(
+        (status = 200, description = "How many products the listing with the same filters has across all pages. Paging parameters are ignored", body = ProductCount),
+        (status = 400, description = "Invalid filter value", body = ErrorEnvelope)
+    ),
+    security((), ("bearer_auth" = []))
+)]
+#[get("/count")]
+pub async fn get_products_count(
+    user: Option<AuthenticatedUser>,
+    pool: web::Data<PgPool>,
+    query: web::Query<ProductQuery>,
+) -> Result<HttpResponse, AppError> {
+    let scope = ProductScope::Public {
+        viewer: user.map(|user| user.0.sub),
+    };
+    let query = count_query(&query, scope)?;
+
+    let count: i64 = sqlx::query_scalar_with(&query.sql, query.arguments()?)
+        .fetch_one(pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(ProductCount { count }))
 }
 
-#[get("/options/shoe-sizes")]
-async fn get_shoe_sizes() -> impl Responder {
-    let data = vec![
-        OptionValue {
-            value: "38".into(),
-            label: "24".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "25".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "26".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "27".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "28".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "29".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "30".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "31".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "32".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "33".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "34".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "35".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "36".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "37".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "38".parse().unwrap(),
-        },
-        OptionValue {
-            value: "38".into(),
-            label: "39".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "40".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "41".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "42".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "43".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "44".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "45".parse().unwrap(),
-        },
-        OptionValue {
-            value: "40".into(),
-            label: "46".parse().unwrap(),
-        },
-    ];
-    HttpResponse::Ok().json(data)
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "The product", body = Product),
+        (status = 304, description = "Unchanged since the `If-None-Match` ETag"),
+        (status = 404, description = "Product not found", body = ErrorEnvelope)
+    ),
+    security((), ("bearer_auth" = []))
+)]
+#[get("/{id:\\d+}")]
+pub async fn get_product_by_id(
+    http_req: HttpRequest,
+    user: Option<AuthenticatedUser>,
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    // Someone else's draft is reported as missing rather than forbidden.
+    let product = sqlx::query_as::<_, Product>(&format!(
+        "{} WHERE p.id = $1 AND (p.status <> 'draft' OR p.user_id = $2) GROUP BY p.id",
+        PRODUCT_SELECT
+    ))
+    .bind(path.into_inner())
+    .bind(user.map(|user| user.0.sub))
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(AppError::internal)?
+    .ok_or_else(|| AppError::NotFound("Product not found".into()))?;
+
+    json_with_etag(&http_req, &product)
 }
 
-#[get("/options/clothing-sizes")]
-async fn get_clothing_sizes() -> impl Responder {
-    let data = vec![
-        OptionValue {
-            value: "S".into(),
-            label: "Small".parse().unwrap(),
-        },
-        OptionValue {
-            value: "M".into(),
-            label: "Medium".parse().unwrap(),
-        },
-        OptionValue {
-            value: "L".into(),
-            label: "Large".parse().unwrap(),
-        },
-        OptionValue {
-            value: "XL".into(),
-            label: "XLarge".parse().unwrap(),
-        },
-        OptionValue {
-            value: "XXL".into(),
-            label: "XXLarge".parse().unwrap(),
-        },
-        OptionValue {
-            value: "XXXL".into(),
-            label: "XXXLarge".parse().unwrap(),
-        },
-        OptionValue {
-            value: "XXXXL".into(),
-            label: "XXXLarge".parse().unwrap(),
-        },
-    ];
-    HttpResponse::Ok().json(data)
+const MAX_SIMILAR_PRODUCTS: i64 = 10;
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "Up to 10 other active products of the same category. Ones sharing the brand, then the color, come first, newest first within each", body = Vec<Product>),
+        (status = 404, description = "Product not found", body = ErrorEnvelope)
+    ),
+    security((), ("bearer_auth" = []))
+)]
+#[get("/{id:\\d+}/similar")]
+pub async fn get_similar_products(
+    user: Option<AuthenticatedUser>,
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let product_id = path.into_inner();
+
+    // Visible on the same terms as get_product_by_id.
+    let (category_id, brand, color): (Option<i32>, Option<String>, Option<String>) =
+        sqlx::query_as(
+            "SELECT category_id, brand, color FROM products
+             WHERE id = $1 AND (status <> 'draft' OR user_id = $2)",
+        )
+        .bind(product_id)
+        .bind(user.map(|user| user.0.sub))
+        .fetch_optional(pool.get_ref())
+        .await
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound("Product not found".into()))?;
+
+    let Some(category_id) = category_id else {
+        return Ok(HttpResponse::Ok().json(Vec::<Product>::new()));
+    };
+
+    let products = sqlx::query_as::<_, Product>(&format!(
+        "{} WHERE p.category_id = $1 AND p.id <> $2 AND p.status = 'active'
+         GROUP BY p.id
+         ORDER BY (lower(p.brand) = lower($3)) IS TRUE DESC,
+                  (p.color = $4) IS TRUE DESC,
+                  p.id DESC
+         LIMIT $5",
+        PRODUCT_SELECT
+    ))
+    .bind(category_id)
+    .bind(product_id)
+    .bind(brand)
+    .bind(color)
+    .bind(MAX_SIMILAR_PRODUCTS)
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(products))
 }
 
-#[get("/options/genders")]
-async fn get_genders() -> impl Responder {
-    let data = vec![
-        OptionValue {
-            value: "male".into(),
-            label: "Чоловіче".into(),
-        },
-        OptionValue {
-            value: "female".into(),
-            label: "Жіноче".into(),
-        },
-        OptionValue {
-            value: "kids".into(),
-            label: "Дитяче".into(),
-        },
-        OptionValue {
-            value: "unisex".into(),
-            label: "Унісекс".into(),
-        },
-    ];
-    HttpResponse::Ok().json(data)
-}
-
-#[derive(Serialize)]
-pub struct ProductCharacteristics {
-    pub shoe_materials: Vec<OptionValue>,
-    pub clothing_materials: Vec<OptionValue>,
-    pub home_types: Vec<OptionValue>,
-    pub home_materials: Vec<OptionValue>,
-    pub book_genres: Vec<OptionValue>,
-    pub book_binding: Vec<OptionValue>,
-    pub book_languages: Vec<OptionValue>,
-    pub garden_types: Vec<OptionValue>,
-    pub electronics_types: Vec<OptionValue>,
-    pub auto_types: Vec<OptionValue>,
-    pub stationery_types: Vec<OptionValue>,
-    pub activity_types: Vec<OptionValue>,
-    pub tourism_types: Vec<OptionValue>,
-    pub water_sports_types: Vec<OptionValue>,
-    pub cycling_types: Vec<OptionValue>,
-    pub climbing_types: Vec<OptionValue>,
-    pub picnic_types: Vec<OptionValue>,
-    pub children_types: Vec<OptionValue>,
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ProductBatchRequest {
+    /// Up to 100 product ids.
+    ids: Vec<i32>,
 }
 
-#[get("/options/materials")]
-async fn get_materials() -> impl Responder {
-    let data = ProductCharacteristics {
-        shoe_materials: vec![
-            OptionValue {
-                value: "suede".into(),
-                label: "Замша".into(),
-            },
-            OptionValue {
-                value: "nubuck".into(),
-                label: "Нубук".into(),
-            },
-            OptionValue {
-                value: "mesh".into(),
-                label: "Сітка".into(),
-            },
-            OptionValue {
-                value: "other_shoes".into(),
-                label: "Інший".into(),
-            },
-        ],
-        clothing_materials: vec![
-            OptionValue {
-                value: "cotton".into(),
-                label: "Бавовна".into(),
-            },
-            OptionValue {
-                value: "wool".into(),
-                label: "Вовна".into(),
-            },
-            OptionValue {
-                value: "linen".into(),
-                label: "Льон".into(),
-            },
-            OptionValue {
-                value: "silk".into(),
-                label: "Шовк".into(),
-            },
-            OptionValue {
-                value: "polyester".into(),
-                label: "Поліестер".into(),
-            },
-            OptionValue {
-                value: "nylon".into(),
-                label: "Нейлон".into(),
-            },
-            OptionValue {
-                value: "acrylic".into(),
-                label: "Акрил".into(),
-            },
-            OptionValue {
-                value: "viscose".into(),
-                label: "Віскоза".into(),
-            },
-            OptionValue {
-                value: "denim".into(),
-                label: "Джинс".into(),
-            },
-            OptionValue {
-                value: "other_clothes".into(),
-                label: "Інший".into(),
-            },
-        ],
-        home_types: vec![
-            OptionValue {
-                value: "dishes".into(),
-                label: "Посуд".into(),
-            },
-            OptionValue {
-                value: "textile".into(),
-                label: "Текстиль".into(),
-            },
-            OptionValue {
-                value: "furniture".into(),
-                label: "Меблі".into(),
-            },
-            OptionValue {
-                value: "decor".into(),
-                label: "Декор".into(),
-            },
-            OptionValue {
-                value: "lighting".into(),
-                label: "Освітлення".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        home_materials: vec![
-            OptionValue {
-                value: "wood".into(),
-                label: "Дерево".into(),
-            },
-            OptionValue {
-                value: "glass".into(),
-                label: "Скло".into(),
-            },
-            OptionValue {
-                value: "ceramic".into(),
-                label: "Кераміка".into(),
-            },
-            OptionValue {
-                value: "metal".into(),
-                label: "Метал".into(),
-            },
-            OptionValue {
-                value: "fabric".into(),
-                label: "Тканина".into(),
-            },
-            OptionValue {
-                value: "plastic".into(),
-                label: "Пластик".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інше".into(),
-            },
-        ],
-        book_genres: vec![
-            OptionValue {
-                value: "fiction".into(),
-                label: "Художня література".into(),
-            },
-            OptionValue {
-                value: "non_fiction".into(),
-                label: "Нехудожня література".into(),
-            },
-            OptionValue {
-                value: "children".into(),
-                label: "Дитяча література".into(),
-            },
-            OptionValue {
-                value: "self_development".into(),
-                label: "Саморозвиток".into(),
-            },
-            OptionValue {
-                value: "business".into(),
-                label: "Бізнес".into(),
-            },
-            OptionValue {
-                value: "history".into(),
-                label: "Історія".into(),
-            },
-            OptionValue {
-                value: "fantasy".into(),
-                label: "Фантастика".into(),
-            },
-            OptionValue {
-                value: "detective".into(),
-                label: "Детектив".into(),
-            },
-            OptionValue {
-                value: "comics".into(),
-                label: "Комікс".into(),
-            },
-            OptionValue {
-                value: "novel".into(),
-                label: "Роман".into(),
-            },
-        ],
-        book_binding: vec![
-            OptionValue {
-                value: "soft".into(),
-                label: "М'яка".into(),
-            },
-            OptionValue {
-                value: "hard".into(),
-                label: "Тверда".into(),
-            },
-        ],
-        book_languages: vec![
-            OptionValue {
-                value: "ukrainian".into(),
-                label: "Українська".into(),
-            },
-            OptionValue {
-                value: "english".into(),
-                label: "Англійська".into(),
-            },
-            OptionValue {
-                value: "german".into(),
-                label: "Німецька".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інше".into(),
-            },
-        ],
-        garden_types: vec![
-            OptionValue {
-                value: "tools".into(),
-                label: "Інвентар (лопата, граблі, сапка, лійка, секатор)".into(),
-            },
-            OptionValue {
-                value: "equipment".into(),
-                label: "Техніка (газонокосарка, оприскувач)".into(),
-            },
-            OptionValue {
-                value: "seeds".into(),
-                label: "Насіння (овочі, квіти, фрукти)".into(),
-            },
-            OptionValue {
-                value: "fertilizers".into(),
-                label: "Добрива (проти шкідників, для росту)".into(),
-            },
-            OptionValue {
-                value: "containers".into(),
-                label: "Ємності (горщик, кашпо, контейнер для розсади, ящик, каністра, відро)".into(),
-            },
-            OptionValue {
-                value: "furniture".into(),
-                label: "Меблі для саду (стільці, лавки, дивани, столи, набори)".into(),
-            },
-            OptionValue {
-                value: "decor".into(),
-                label: "Декор (статуетки, фонтани, камені, плитка)".into(),
-            },
-            OptionValue {
-                value: "lighting".into(),
-                label: "Освітлення (сонячна лампа, ліхтар, гірлянда)".into(),
-            },
-            OptionValue {
-                value: "fencing".into(),
-                label: "Огорожі (пластикові, дерев'яні, металічні)".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        electronics_types: vec![
-            OptionValue {
-                value: "phone".into(),
-                label: "Телефон".into(),
-            },
-            OptionValue {
-                value: "laptop".into(),
-                label: "Ноутбук".into(),
-            },
-            OptionValue {
-                value: "tablet".into(),
-                label: "Планшет".into(),
-            },
-            OptionValue {
-                value: "headphones".into(),
-                label: "Навушники".into(),
-            },
-            OptionValue {
-                value: "watch".into(),
-                label: "Годинник".into(),
-            },
-            OptionValue {
-                value: "camera".into(),
-                label: "Фотоапарат".into(),
-            },
-            OptionValue {
-                value: "tv".into(),
-                label: "Телевізор".into(),
-            },
-            OptionValue {
-                value: "fridge".into(),
-                label: "Холодильник".into(),
-            },
-            OptionValue {
-                value: "dishwasher".into(),
-                label: "Посудомийка".into(),
-            },
-            OptionValue {
-                value: "game_console".into(),
-                label: "Приставка".into(),
-            },
-            OptionValue {
-                value: "washing_machine".into(),
-                label: "Пральна машина".into(),
-            },
-            OptionValue {
-                value: "speakers".into(),
-                label: "Колонки".into(),
-            },
-            OptionValue {
-                value: "sewing_machine".into(),
-                label: "Швейна машинка".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        auto_types: vec![
-            OptionValue {
-                value: "accessories".into(),
-                label: "Аксесуари".into(),
-            },
-            OptionValue {
-                value: "parts".into(),
-                label: "Запчастини".into(),
-            },
-            OptionValue {
-                value: "electronics".into(),
-                label: "Автоелектроніка".into(),
-            },
-            OptionValue {
-                value: "fluids".into(),
-                label: "Масло та рідини".into(),
-            },
-            OptionValue {
-                value: "care".into(),
-                label: "Догляд".into(),
-            },
-            OptionValue {
-                value: "tires".into(),
-                label: "Шини".into(),
-            },
-            OptionValue {
-                value: "rims".into(),
-                label: "Диски".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        stationery_types: vec![
-            OptionValue {
-                value: "writing".into(),
-                label: "Пишучі прилади (гелеві ручки, кулькові ручки, механічні олівці, графітні олівці, кольорові олівці, маркери)".into(),
-            },
-            OptionValue {
-                value: "paper".into(),
-                label: "Паперова продукція (зошит в клітинку, зошит в лінійку, щоденник, блокнот, калька, стікери для нотаток, папір для друку, картон/ватман)".into(),
-            },
-            OptionValue {
-                value: "organization".into(),
-                label: "Організація документів (папки, файли, розділювачі, обкладинки, підставки для ручок, органайзери)".into(),
-            },
-            OptionValue {
-                value: "office".into(),
-                label: "Офісне приладдя (степлер, скоби, скрепки, кнопки, клей-олівець, ножиці, лінійка, калькулятор)".into(),
-            },
-            OptionValue {
-                value: "art".into(),
-                label: "Творчість (альбом для малювання, фарби, художні кисті, фломастери, пластилін, крейда, наліпки, клей)".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        activity_types: vec![
-            OptionValue {
-                value: "tourism".into(),
-                label: "Туризм та походи".into(),
-            },
-            OptionValue {
-                value: "water_sports".into(),
-                label: "Водні види спорту".into(),
-            },
-            OptionValue {
-                value: "cycling".into(),
-                label: "Велоспорт".into(),
-            },
-            OptionValue {
-                value: "climbing".into(),
-                label: "Альпінізм".into(),
-            },
-            OptionValue {
-                value: "picnic".into(),
-                label: "Пікнік".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        tourism_types: vec![
-            OptionValue {
-                value: "tent".into(),
-                label: "Намет".into(),
-            },
-            OptionValue {
-                value: "sleeping_bag".into(),
-                label: "Спальний мішок".into(),
-            },
-            OptionValue {
-                value: "burner".into(),
-                label: "Пальник".into(),
-            },
-            OptionValue {
-                value: "backpack".into(),
-                label: "Рюкзак".into(),
-            },
-            OptionValue {
-                value: "sleeping_pad".into(),
-                label: "Каремат".into(),
-            },
-            OptionValue {
-                value: "dishes".into(),
-                label: "Посуд".into(),
-            },
-            OptionValue {
-                value: "compass".into(),
-                label: "Компас".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        water_sports_types: vec![
-            OptionValue {
-                value: "goggles".into(),
-                label: "Водні окуляри та маски".into(),
-            },
-            OptionValue {
-                value: "fins".into(),
-                label: "Ласти".into(),
-            },
-            OptionValue {
-                value: "boards".into(),
-                label: "Дошки".into(),
-            },
-            OptionValue {
-                value: "paddles".into(),
-                label: "Весла".into(),
-            },
-            OptionValue {
-                value: "life_jackets".into(),
-                label: "Рятувальні жилети".into(),
-            },
-            OptionValue {
-                value: "kayaks".into(),
-                label: "Байдарки".into(),
-            },
-            OptionValue {
-                value: "pump".into(),
-                label: "Насос".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        cycling_types: vec![
-            OptionValue {
-                value: "bicycle".into(),
-                label: "Велосипед".into(),
-            },
-            OptionValue {
-                value: "wheels".into(),
-                label: "Колеса".into(),
-            },
-            OptionValue {
-                value: "pump".into(),
-                label: "Насос".into(),
-            },
-            OptionValue {
-                value: "helmet".into(),
-                label: "Шолом".into(),
-            },
-            OptionValue {
-                value: "lights".into(),
-                label: "Ліхтарі".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        climbing_types: vec![
-            OptionValue {
-                value: "climbing_shoes".into(),
-                label: "Скельники".into(),
-            },
-            OptionValue {
-                value: "protection".into(),
-                label: "Страхування".into(),
-            },
-            OptionValue {
-                value: "carabiner".into(),
-                label: "Карабін".into(),
-            },
-            OptionValue {
-                value: "rope".into(),
-                label: "Мотузка".into(),
-            },
-            OptionValue {
-                value: "helmet".into(),
-                label: "Каска".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        picnic_types: vec![
-            OptionValue {
-                value: "plaid".into(),
-                label: "Плед".into(),
-            },
-            OptionValue {
-                value: "dishes".into(),
-                label: "Посуд".into(),
-            },
-            OptionValue {
-                value: "burner".into(),
-                label: "Пальник".into(),
-            },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
-            },
-        ],
-        children_types: vec![
-            OptionValue {
-                value: "clothes".into(),
-                label: "Одяг (комбінезон, футболки, штани, боді, піжама)".into(),
-            },
-            OptionValue {
-                value: "shoes".into(),
-                label: "Взуття (повсякденне, зимове, гумові чоботи, інше)".into(),
-            },
-            OptionValue {
-                value: "toys".into(),
-                label: "Іграшки (м'які, розвиваючі, конструктори, для вулиці, інтерактивні, інші)".into(),
-            },
-            OptionValue {
-                value: "care".into(),
-                label: "Догляд (підгузки, ванночки, термометри, шампуні, щітки, інші)".into(),
-            },
-            OptionValue {
-                value: "education".into(),
-                label: "Навчання та творчість (розмальовка, для ліплення, пазли, навчальні зошити, абетка, цифри, інше)".into(),
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    request_body = ProductBatchRequest,
+    responses(
+        (status = 200, description = "The products that exist, in the order of `ids`", body = Vec<Product>),
+        (status = 400, description = "Too many ids", body = ErrorEnvelope)
+    ),
+    security((), ("bearer_auth" = []))
+)]
+#[post("/batch")]
+pub async fn get_products_batch(
+    user: Option<AuthenticatedUser>,
+    pool: web::Data<PgPool>,
+    req: web::Json<ProductBatchRequest>,
+) -> Result<HttpResponse, AppError> {
+    if req.ids.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "At most {} ids can be fetched at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    // Missing products and other users' drafts are left out, like a 404 from
+    // get_product_by_id would.
+    let products = sqlx::query_as::<_, Product>(&format!(
+        "{} WHERE p.id = ANY($1) AND (p.status <> 'draft' OR p.user_id = $2) \
+         GROUP BY p.id ORDER BY array_position($1, p.id)",
+        PRODUCT_SELECT
+    ))
+    .bind(&req.ids)
+    .bind(user.map(|user| user.0.sub))
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(products))
+}
+
+/// Shorter queries can't use the trigram indexes and match too much to be useful.
+const MIN_SUGGEST_QUERY_LENGTH: usize = 3;
+const MAX_SUGGESTIONS: i64 = 10;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SuggestQuery {
+    /// What the user has typed so far. Under 3 characters gives no suggestions.
+    #[serde(default)]
+    q: String,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct Suggestion {
+    text: String,
+    /// `title` or `brand`, i.e. what `text` was found in.
+    kind: String,
+}
+
+/// `value` with the LIKE wildcards escaped, so it only matches itself.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(SuggestQuery),
+    responses(
+        (status = 200, description = "Up to 10 titles and brands of active products containing `q`. Ones starting with it come first, then the most listed", body = Vec<Suggestion>)
+    )
+)]
+#[get("/suggest")]
+pub async fn suggest(
+    query: web::Query<SuggestQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let q = query.q.trim().to_lowercase();
+
+    if q.chars().count() < MIN_SUGGEST_QUERY_LENGTH {
+        return Ok(HttpResponse::Ok().json(Vec::<Suggestion>::new()));
+    }
+
+    // Spellings that differ only in case are one suggestion.
+    let suggestions = sqlx::query_as::<_, Suggestion>(
+        "SELECT text, kind FROM (
+            SELECT min(title) AS text, 'title' AS kind, COUNT(*) AS listings
+            FROM products
+            WHERE status = 'active' AND title ILIKE $1
+            GROUP BY lower(title)
+            UNION ALL
+            SELECT min(brand), 'brand', COUNT(*)
+            FROM products
+            WHERE status = 'active' AND brand ILIKE $1
+            GROUP BY lower(brand)
+         ) matches
+         ORDER BY starts_with(lower(text), $2) DESC, listings DESC, text
+         LIMIT $3",
+    )
+    .bind(format!("%{}%", escape_like(&q)))
+    .bind(&q)
+    .bind(MAX_SUGGESTIONS)
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+const DEFAULT_BRANDS_LIMIT: i64 = 50;
+const MAX_BRANDS_LIMIT: i64 = 200;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BrandQuery {
+    /// Only brands of products in this category.
+    category: Option<i32>,
+    /// 50 unless set. Values above 200 are treated as 200.
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct Brand {
+    brand: String,
+    /// Active products of the brand.
+    product_count: i64,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(BrandQuery),
+    responses(
+        (status = 200, description = "Brands of active products, most listed first", body = Vec<Brand>),
+        (status = 400, description = "Invalid limit", body = ErrorEnvelope)
+    )
+)]
+#[get("/brands")]
+pub async fn get_brands(
+    query: web::Query<BrandQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let limit = match query.limit {
+        None => DEFAULT_BRANDS_LIMIT,
+        Some(limit) if limit < 1 => {
+            return Err(AppError::BadRequest("limit must be at least 1".into()));
+        }
+        Some(limit) => limit.min(MAX_BRANDS_LIMIT),
+    };
+
+    let brands = sqlx::query_as::<_, Brand>(
+        "SELECT brand, COUNT(*) AS product_count
+         FROM products
+         WHERE status = 'active' AND brand IS NOT NULL
+           AND ($1::int IS NULL OR category_id = $1)
+         GROUP BY brand
+         ORDER BY product_count DESC, brand
+         LIMIT $2",
+    )
+    .bind(query.category)
+    .bind(limit)
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(brands))
+}
+
+/// Fails with 404 if the product doesn't exist and 403 if it isn't `user_id`'s.
+/// Inside a transaction the product row stays locked until it ends.
+async fn ensure_product_owner<'e, E>(
+    executor: E,
+    product_id: i32,
+    user_id: &Uuid,
+) -> Result<(), AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let owner: Option<Uuid> =
+        sqlx::query_scalar("SELECT user_id FROM products WHERE id = $1 FOR UPDATE")
+            .bind(product_id)
+            .fetch_optional(executor)
+            .await
+            .map_err(AppError::internal)?;
+
+    match owner {
+        None => Err(AppError::NotFound("Product not found".into())),
+        Some(owner) if owner != *user_id => Err(AppError::Forbidden(
+            "You can only change your own products".into(),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateStatusRequest {
+    status: ProductStatus,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body = UpdateStatusRequest,
+    responses(
+        (status = 200, description = "The resulting status and quantity. Selling one of several items keeps the product active"),
+        (status = 400, description = "Status can't be set to draft", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope),
+        (status = 409, description = "The product is a draft and has to be published first", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/{id}/status")]
+pub async fn update_status(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    req: web::Json<UpdateStatusRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    if req.status == ProductStatus::Draft {
+        return Err(AppError::BadRequest(
+            "A published product can't be turned back into a draft".into(),
+        ));
+    }
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    // Selling takes one item off the stock and only marks the product sold once
+    // none are left; relisting puts at least one back. Drafts only leave that
+    // state through `publish`, which checks they are complete.
+    let (status, quantity): (String, i32) = sqlx::query_as(
+        "UPDATE products SET
+            quantity = CASE $1
+                WHEN 'sold' THEN GREATEST(quantity - 1, 0)
+                WHEN 'active' THEN GREATEST(quantity, 1)
+                ELSE quantity
+            END,
+            status = CASE WHEN $1 = 'sold' AND quantity > 1 THEN 'active' ELSE $1 END
+         WHERE id = $2 AND status <> 'draft'
+         RETURNING status, quantity",
+    )
+    .bind(req.status.to_string())
+    .bind(product_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::internal)?
+    .ok_or_else(|| AppError::Conflict("Drafts have to be published first".into()))?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": product_id,
+        "status": status,
+        "quantity": quantity,
+    })))
+}
+
+/// Fields to change; anything left out keeps its current value.
+#[derive(Deserialize, ToSchema)]
+pub struct EditProductRequest {
+    #[schema(max_length = 120)]
+    title: Option<String>,
+    #[schema(max_length = 4000)]
+    description: Option<String>,
+    category_id: Option<i32>,
+    brand: Option<String>,
+    condition: Option<ProductCondition>,
+    price: Option<f64>,
+    currency: Option<Currency>,
+    phone_number: Option<String>,
+    /// Restocks or corrects the number of items left; at least 1.
+    quantity: Option<i32>,
+    color: Option<String>,
+    shoe_size: Option<String>,
+    clothing_size: Option<String>,
+    gender: Option<String>,
+    material: Option<String>,
+    city: Option<String>,
+    /// Must be sent together with `longitude`.
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// Replaces the delivery options; an empty list removes them all.
+    delivery_option_ids: Option<Vec<i32>>,
+    /// Replaces the payment options; an empty list removes them all.
+    payment_option_ids: Option<Vec<i32>>,
+}
+
+impl EditProductRequest {
+    /// Also trims the title and description and normalizes the brand.
+    fn validate(&mut self) -> Result<(), AppError> {
+        let mut errors = FieldErrors::default();
+
+        self.brand = self.brand.as_deref().and_then(normalize_brand);
+
+        if let Some(title) = &self.title {
+            self.title = errors.check("title", trimmed_text("Title", title, MAX_TITLE_LENGTH));
+        }
+        if let Some(description) = &self.description {
+            self.description = errors.check(
+                "description",
+                trimmed_text("Description", description, MAX_DESCRIPTION_LENGTH),
+            );
+        }
+        if let Some(phone_number) = &self.phone_number {
+            errors.check("phone_number", validate_phone_number(phone_number));
+        }
+        if let Some(price) = self.price {
+            errors.check("price", validate_price(price));
+        }
+        if let Some(quantity) = self.quantity {
+            errors.check("quantity", validate_quantity(quantity));
+        }
+
+        for (field, value, sets) in [
+            ("color", &self.color, &[COLORS][..]),
+            ("shoe_size", &self.shoe_size, &[SHOE_SIZES]),
+            ("clothing_size", &self.clothing_size, &[CLOTHING_SIZES]),
+            ("gender", &self.gender, &[GENDERS]),
+            ("material", &self.material, MATERIAL_SETS),
+        ] {
+            errors.check(field, validate_option(field, value.as_deref(), sets));
+        }
+
+        let field = if self.latitude.is_none() {
+            "latitude"
+        } else {
+            "longitude"
+        };
+        errors.check(field, coordinate_pair(self.latitude, self.longitude));
+
+        errors.into_result()
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body = EditProductRequest,
+    responses(
+        (status = 200, description = "The updated product", body = Product),
+        (status = 400, description = "Invalid field or option id, or the edit would leave a published product incomplete", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/{id}")]
+pub async fn edit(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    mut req: web::Json<EditProductRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    req.validate()?;
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+    ensure_category_exists(&mut tx, req.category_id).await?;
+
+    let brand = canonical_brand(&mut tx, req.brand.take()).await?;
+
+    let status: String = sqlx::query_scalar(
+        "UPDATE products SET
+            title = COALESCE($1, title),
+            description = COALESCE($2, description),
+            category_id = COALESCE($3, category_id),
+            brand = COALESCE($4, brand),
+            condition = COALESCE($5, condition),
+            price = COALESCE($6, price),
+            phone_number = COALESCE($7, phone_number),
+            color = COALESCE($8, color),
+            shoe_size = COALESCE($9, shoe_size),
+            clothing_size = COALESCE($10, clothing_size),
+            gender = COALESCE($11, gender),
+            material = COALESCE($12, material),
+            city = COALESCE($13, city),
+            latitude = COALESCE($14, latitude),
+            longitude = COALESCE($15, longitude),
+            quantity = COALESCE($16, quantity),
+            currency = COALESCE($17, currency)
+         WHERE id = $18
+         RETURNING status",
+    )
+    .bind(&req.title)
+    .bind(&req.description)
+    .bind(req.category_id)
+    .bind(&brand)
+    .bind(req.condition.map(|condition| condition.to_string()))
+    .bind(req.price)
+    .bind(&req.phone_number)
+    .bind(&req.color)
+    .bind(&req.shoe_size)
+    .bind(&req.clothing_size)
+    .bind(&req.gender)
+    .bind(&req.material)
+    .bind(&req.city)
+    .bind(req.latitude)
+    .bind(req.longitude)
+    .bind(req.quantity)
+    .bind(req.currency.map(|currency| currency.to_string()))
+    .bind(product_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    if let Some(ids) = &req.delivery_option_ids {
+        replace_product_options(&mut tx, product_id, OptionKind::Delivery, ids).await?;
+    }
+    if let Some(ids) = &req.payment_option_ids {
+        replace_product_options(&mut tx, product_id, OptionKind::Payment, ids).await?;
+    }
+
+    // Published products have to stay publishable after the edit.
+    if status != ProductStatus::Draft.to_string() {
+        load_publish_fields(&mut tx, product_id).await?.validate()?;
+    }
+
+    let product =
+        sqlx::query_as::<_, Product>(&format!("{} WHERE p.id = $1 GROUP BY p.id", PRODUCT_SELECT))
+            .bind(product_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(product))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "Draft published"),
+        (status = 400, description = "The draft is missing required fields or photos", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope),
+        (status = 409, description = "The product is already published", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/{id}/publish")]
+pub async fn publish(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let status: String = sqlx::query_scalar("SELECT status FROM products WHERE id = $1")
+        .bind(product_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    if status != ProductStatus::Draft.to_string() {
+        return Err(AppError::Conflict("Product is already published".into()));
+    }
+
+    load_publish_fields(&mut tx, product_id).await?.validate()?;
+
+    sqlx::query("UPDATE products SET status = $1 WHERE id = $2")
+        .bind(ProductStatus::Active.to_string())
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::internal)?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": product_id,
+        "status": ProductStatus::Active,
+    })))
+}
+
+/// Multipart form accepted by `add_images`. Only used for the API docs.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct AddImagesForm {
+    /// One or more images, jpeg, png or webp unless `ALLOWED_IMAGE_TYPES` says otherwise.
+    #[schema(value_type = Vec<String>, format = Binary)]
+    photos: Vec<Vec<u8>>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body(content = AddImagesForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Images added after the existing ones"),
+        (status = 400, description = "No photos or an invalid file", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope),
+        (status = 413, description = "A photo or the whole form is too large", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/{id}/images")]
+pub async fn add_images(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    payload: Multipart,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    let photos = read_product_form(payload).await?.photos;
+
+    if photos.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one photo is required".into(),
+        ));
+    }
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let next_position: i32 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM product_images WHERE product_id = $1",
+    )
+    .bind(product_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    store_photos(&mut tx, product_id, photos, next_position).await?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Created().body("Images added"))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReorderImagesRequest {
+    image_ids: Vec<i32>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body = ReorderImagesRequest,
+    responses(
+        (status = 200, description = "Images reordered"),
+        (status = 400, description = "The ids don't match the product's images", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/{id}/images/reorder")]
+pub async fn reorder_images(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    req: web::Json<ReorderImagesRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let mut current: Vec<i32> =
+        sqlx::query_scalar("SELECT id FROM product_images WHERE product_id = $1")
+            .bind(product_id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    let mut requested = req.image_ids.clone();
+    current.sort_unstable();
+    requested.sort_unstable();
+
+    // The new order has to mention every image of the product exactly once.
+    if current != requested {
+        return Err(AppError::BadRequest(
+            "image_ids must list every image of the product exactly once".into(),
+        ));
+    }
+
+    let positions: Vec<i32> = (0..req.image_ids.len() as i32).collect();
+
+    sqlx::query(
+        "UPDATE product_images SET position = data.position
+         FROM UNNEST($1::int[], $2::int[]) AS data(id, position)
+         WHERE product_images.id = data.id AND product_images.product_id = $3",
+    )
+    .bind(&req.image_ids)
+    .bind(&positions)
+    .bind(product_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().body("Images reordered"))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+        ("image_id" = i32, Path, description = "Image id")
+    ),
+    responses(
+        (status = 200, description = "Image deleted"),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product or image not found", body = ErrorEnvelope),
+        (status = 409, description = "The product's last image can't be deleted", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[delete("/{id}/images/{image_id}")]
+pub async fn delete_image(
+    user: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let (product_id, image_id) = path.into_inner();
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let image_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM product_images WHERE product_id = $1")
+            .bind(product_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    let url: String = sqlx::query_scalar(
+        "DELETE FROM product_images WHERE id = $1 AND product_id = $2 RETURNING url",
+    )
+    .bind(image_id)
+    .bind(product_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::internal)?
+    .ok_or_else(|| AppError::NotFound("Image not found".into()))?;
+
+    if image_count <= 1 {
+        return Err(AppError::Conflict(
+            "A product must keep at least one image".into(),
+        ));
+    }
+
+    // Close the gap left by the deleted image so positions stay 0..n.
+    sqlx::query(
+        "UPDATE product_images SET position = ranked.position
+         FROM (
+             SELECT id, ROW_NUMBER() OVER (ORDER BY position) - 1 AS position
+             FROM product_images
+             WHERE product_id = $1
+         ) AS ranked
+         WHERE product_images.id = ranked.id",
+    )
+    .bind(product_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    delete_unreferenced_uploads(pool.get_ref(), vec![url]).await;
+
+    Ok(HttpResponse::Ok().body("Image deleted"))
+}
+
+/// Multipart form accepted by `put_video`. Only used for the API docs.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct PutVideoForm {
+    /// An mp4 or webm file, 20 MB at most unless configured otherwise.
+    #[schema(value_type = String, format = Binary)]
+    video: Vec<u8>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VideoResponse {
+    video_url: String,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body(content = PutVideoForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Video added, replacing the previous one", body = VideoResponse),
+        (status = 400, description = "No video or not an mp4 or webm file", body = ErrorEnvelope),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope),
+        (status = 413, description = "The video is too large", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[put("/{id}/video")]
+pub async fn put_video(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    payload: Multipart,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    let video = read_product_form(payload)
+        .await?
+        .video
+        .ok_or_else(|| AppError::BadRequest("A video is required".into()))?;
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let (video_url, replaced) = store_video(&mut tx, product_id, video).await?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    if let Some(replaced) = replaced {
+        delete_unreferenced_uploads(pool.get_ref(), vec![replaced]).await;
+    }
+
+    Ok(HttpResponse::Ok().json(VideoResponse { video_url }))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(("id" = i32, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "Video deleted"),
+        (status = 403, description = "Not the owner of the product", body = ErrorEnvelope),
+        (status = 404, description = "Product or video not found", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[delete("/{id}/video")]
+pub async fn delete_video(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    let mut tx = pool.begin().await.map_err(AppError::internal)?;
+
+    ensure_product_owner(&mut *tx, product_id, &user.0.sub).await?;
+
+    let url: String =
+        sqlx::query_scalar("DELETE FROM product_videos WHERE product_id = $1 RETURNING url")
+            .bind(product_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(AppError::internal)?
+            .ok_or_else(|| AppError::NotFound("Video not found".into()))?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    delete_unreferenced_uploads(pool.get_ref(), vec![url]).await;
+
+    Ok(HttpResponse::Ok().body("Video deleted"))
+}
+
+/// Deletes the S3 objects behind `urls` that no product image or video uses
+/// anymore. Uploads are shared by content, so another product may still show
+/// the same file. Call after the rows are gone; failures are only logged.
+pub(crate) async fn delete_unreferenced_uploads(pool: &PgPool, urls: Vec<String>) {
+    let unreferenced: Vec<String> = match sqlx::query_scalar(
+        "SELECT DISTINCT u.url FROM UNNEST($1::text[]) AS u(url)
+         WHERE NOT EXISTS (SELECT 1 FROM product_images pi WHERE pi.url = u.url)
+           AND NOT EXISTS (SELECT 1 FROM product_videos pv WHERE pv.url = u.url)",
+    )
+    .bind(&urls)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(unreferenced) => unreferenced,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to look up unreferenced product uploads");
+            return;
+        }
+    };
+
+    for url in &unreferenced {
+        if let Some(key) = key_from_url(url)
+            && let Err(e) = delete_from_s3(AWS_MARKETPLACE_BUCKET.as_str(), key).await
+        {
+            tracing::warn!(error = %e, key, "Failed to delete S3 object of product upload");
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OptionValue {
+    pub value: String,
+    /// In the language named by the `Content-Language` response header.
+    pub label: String,
+}
+
+/// Languages option labels are available in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Locale {
+    #[default]
+    Ukrainian,
+    English,
+}
+
+impl Locale {
+    /// Only the primary subtag matters, so `en-GB` is English.
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim();
+
+        if primary.eq_ignore_ascii_case("uk") {
+            Some(Locale::Ukrainian)
+        } else if primary.eq_ignore_ascii_case("en") {
+            Some(Locale::English)
+        } else {
+            None
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Locale::Ukrainian => "uk",
+            Locale::English => "en",
+        }
+    }
+
+    fn from_accept_language(req: &HttpRequest) -> Option<Self> {
+        AcceptLanguage::parse(req)
+            .ok()?
+            .ranked()
+            .iter()
+            .find_map(|preference| match preference {
+                Preference::Specific(tag) => Locale::from_tag(tag.primary_language()),
+                Preference::Any => None,
+            })
+    }
+}
+
+/// Labels are in the language of `lang` or, without it, the best match for
+/// `Accept-Language`. Ukrainian is the fallback.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LocaleQuery {
+    /// Language tag, `uk` or `en`.
+    lang: Option<String>,
+}
+
+impl FromRequest for Locale {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let requested = web::Query::<LocaleQuery>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.into_inner().lang)
+            .and_then(|lang| Locale::from_tag(&lang));
+
+        ready(Ok(requested
+            .or_else(|| Locale::from_accept_language(req))
+            .unwrap_or_default()))
+    }
+}
+
+/// An option list in `locale`, marked so that caches keep one copy per language.
+fn localized(locale: Locale, body: impl Serialize) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_LANGUAGE, locale.tag()))
+        .insert_header((header::VARY, "Accept-Language"))
+        .json(body)
+}
+
+/// `(value, Ukrainian label, English label)` triples. The value is what gets
+/// stored on the product, the label is what the app shows.
+type OptionSet = &'static [(&'static str, &'static str, &'static str)];
+
+const COLORS: OptionSet = &[
+    ("red", "Червоний", "Red"),
+    ("pink", "Рожевий", "Pink"),
+    ("blue", "Синій", "Blue"),
+    ("yellow", "Жовтий", "Yellow"),
+    ("grey", "Сірий", "Grey"),
+    ("black", "Чорний", "Black"),
+    ("white", "Білий", "White"),
+    ("other", "Інший", "Other"),
+];
+
+const SHOE_SIZES: OptionSet = &[
+    ("24", "24", "24"),
+    ("25", "25", "25"),
+    ("26", "26", "26"),
+    ("27", "27", "27"),
+    ("28", "28", "28"),
+    ("29", "29", "29"),
+    ("30", "30", "30"),
+    ("31", "31", "31"),
+    ("32", "32", "32"),
+    ("33", "33", "33"),
+    ("34", "34", "34"),
+    ("35", "35", "35"),
+    ("36", "36", "36"),
+    ("37", "37", "37"),
+    ("38", "38", "38"),
+    ("39", "39", "39"),
+    ("40", "40", "40"),
+    ("41", "41", "41"),
+    ("42", "42", "42"),
+    ("43", "43", "43"),
+    ("44", "44", "44"),
+    ("45", "45", "45"),
+    ("46", "46", "46"),
+];
+
+const CLOTHING_SIZES: OptionSet = &[
+    ("S", "Small", "Small"),
+    ("M", "Medium", "Medium"),
+    ("L", "Large", "Large"),
+    ("XL", "XLarge", "XLarge"),
+    ("XXL", "XXLarge", "XXLarge"),
+    ("XXXL", "XXXLarge", "XXXLarge"),
+    ("XXXXL", "XXXXLarge", "XXXXLarge"),
+];
+
+const GENDERS: OptionSet = &[
+    ("male", "Чоловіче", "Men's"),
+    ("female", "Жіноче", "Women's"),
+    ("kids", "Дитяче", "Kids'"),
+    ("unisex", "Унісекс", "Unisex"),
+];
+
+const SHOE_MATERIALS: OptionSet = &[
+    ("suede", "Замша", "Suede"),
+    ("nubuck", "Нубук", "Nubuck"),
+    ("mesh", "Сітка", "Mesh"),
+    ("other_shoes", "Інший", "Other"),
+];
+
+const CLOTHING_MATERIALS: OptionSet = &[
+    ("cotton", "Бавовна", "Cotton"),
+    ("wool", "Вовна", "Wool"),
+    ("linen", "Льон", "Linen"),
+    ("silk", "Шовк", "Silk"),
+    ("polyester", "Поліестер", "Polyester"),
+    ("nylon", "Нейлон", "Nylon"),
+    ("acrylic", "Акрил", "Acrylic"),
+    ("viscose", "Віскоза", "Viscose"),
+    ("denim", "Джинс", "Denim"),
+    ("other_clothes", "Інший", "Other"),
+];
+
+const HOME_TYPES: OptionSet = &[
+    ("dishes", "Посуд", "Dishes"),
+    ("textile", "Текстиль", "Textiles"),
+    ("furniture", "Меблі", "Furniture"),
+    ("decor", "Декор", "Decor"),
+    ("lighting", "Освітлення", "Lighting"),
+    ("other", "Інший", "Other"),
+];
+
+const HOME_MATERIALS: OptionSet = &[
+    ("wood", "Дерево", "Wood"),
+    ("glass", "Скло", "Glass"),
+    ("ceramic", "Кераміка", "Ceramic"),
+    ("metal", "Метал", "Metal"),
+    ("fabric", "Тканина", "Fabric"),
+    ("plastic", "Пластик", "Plastic"),
+    ("other", "Інше", "Other"),
+];
+
+const BOOK_GENRES: OptionSet = &[
+    ("fiction", "Художня література", "Fiction"),
+    ("non_fiction", "Нехудожня література", "Non-fiction"),
+    ("children", "Дитяча література", "Children's books"),
+    ("self_development", "Саморозвиток", "Self-development"),
+    ("business", "Бізнес", "Business"),
+    ("history", "Історія", "History"),
+    ("fantasy", "Фантастика", "Science fiction"),
+    ("detective", "Детектив", "Detective"),
+    ("comics", "Комікс", "Comics"),
+    ("novel", "Роман", "Novel"),
+];
+
+const BOOK_BINDING: OptionSet = &[
+    ("soft", "М'яка", "Paperback"),
+    ("hard", "Тверда", "Hardcover"),
+];
+
+const BOOK_LANGUAGES: OptionSet = &[
+    ("ukrainian", "Українська", "Ukrainian"),
+    ("english", "Англійська", "English"),
+    ("german", "Німецька", "German"),
+    ("other", "Інше", "Other"),
+];
+
+const GARDEN_TYPES: OptionSet = &[
+    (
+        "tools",
+        "Інвентар (лопата, граблі, сапка, лійка, секатор)",
+        "Tools (shovel, rake, hoe, watering can, pruner)",
+    ),
+    (
+        "equipment",
+        "Техніка (газонокосарка, оприскувач)",
+        "Machinery (lawn mower, sprayer)",
+    ),
+    (
+        "seeds",
+        "Насіння (овочі, квіти, фрукти)",
+        "Seeds (vegetables, flowers, fruit)",
+    ),
+    (
+        "fertilizers",
+        "Добрива (проти шкідників, для росту)",
+        "Fertilizers (pest control, growth)",
+    ),
+    (
+        "containers",
+        "Ємності (горщик, кашпо, контейнер для розсади, ящик, каністра, відро)",
+        "Containers (pot, planter, seedling tray, box, canister, bucket)",
+    ),
+    (
+        "furniture",
+        "Меблі для саду (стільці, лавки, дивани, столи, набори)",
+        "Garden furniture (chairs, benches, sofas, tables, sets)",
+    ),
+    (
+        "decor",
+        "Декор (статуетки, фонтани, камені, плитка)",
+        "Decor (figurines, fountains, stones, tiles)",
+    ),
+    (
+        "lighting",
+        "Освітлення (сонячна лампа, ліхтар, гірлянда)",
+        "Lighting (solar lamp, lantern, string lights)",
+    ),
+    (
+        "fencing",
+        "Огорожі (пластикові, дерев'яні, металічні)",
+        "Fencing (plastic, wooden, metal)",
+    ),
+    ("other", "Інший", "Other"),
+];
+
+const ELECTRONICS_TYPES: OptionSet = &[
+    ("phone", "Телефон", "Phone"),
+    ("laptop", "Ноутбук", "Laptop"),
+    ("tablet", "Планшет", "Tablet"),
+    ("headphones", "Навушники", "Headphones"),
+    ("watch", "Годинник", "Watch"),
+    ("camera", "Фотоапарат", "Camera"),
+    ("tv", "Телевізор", "TV"),
+    ("fridge", "Холодильник", "Fridge"),
+    ("dishwasher", "Посудомийка", "Dishwasher"),
+    ("game_console", "Приставка", "Game console"),
+    ("washing_machine", "Пральна машина", "Washing machine"),
+    ("speakers", "Колонки", "Speakers"),
+    ("sewing_machine", "Швейна машинка", "Sewing machine"),
+    ("other", "Інший", "Other"),
+];
+
+const AUTO_TYPES: OptionSet = &[
+    ("accessories", "Аксесуари", "Accessories"),
+    ("parts", "Запчастини", "Parts"),
+    ("electronics", "Автоелектроніка", "Car electronics"),
+    ("fluids", "Масло та рідини", "Oils and fluids"),
+    ("care", "Догляд", "Care"),
+    ("tires", "Шини", "Tires"),
+    ("rims", "Диски", "Rims"),
+    ("other", "Інший", "Other"),
+];
+
+const STATIONERY_TYPES: OptionSet = &[
+    (
+        "writing",
+        "Пишучі прилади (гелеві ручки, кулькові ручки, механічні олівці, графітні олівці, кольорові олівці, маркери)",
+        "Writing instruments (gel pens, ballpoint pens, mechanical pencils, graphite pencils, colored pencils, markers)",
+    ),
+    (
+        "paper",
+        "Паперова продукція (зошит в клітинку, зошит в лінійку, щоденник, блокнот, калька, стікери для нотаток, папір для друку, картон/ватман)",
+        "Paper products (squared notebook, ruled notebook, diary, notepad, tracing paper, sticky notes, printer paper, cardboard/drawing paper)",
+    ),
+    (
+        "organization",
+        "Організація документів (папки, файли, розділювачі, обкладинки, підставки для ручок, органайзери)",
+        "Document organization (folders, sleeves, dividers, covers, pen holders, organizers)",
+    ),
+    (
+        "office",
+        "Офісне приладдя (степлер, скоби, скрепки, кнопки, клей-олівець, ножиці, лінійка, калькулятор)",
+        "Office supplies (stapler, staples, paper clips, pins, glue stick, scissors, ruler, calculator)",
+    ),
+    (
+        "art",
+        "Творчість (альбом для малювання, фарби, художні кисті, фломастери, пластилін, крейда, наліпки, клей)",
+        "Arts and crafts (sketchbook, paints, brushes, felt-tip pens, modeling clay, chalk, stickers, glue)",
+    ),
+    ("other", "Інший", "Other"),
+];
+
+const ACTIVITY_TYPES: OptionSet = &[
+    ("tourism", "Туризм та походи", "Hiking and camping"),
+    ("water_sports", "Водні види спорту", "Water sports"),
+    ("cycling", "Велоспорт", "Cycling"),
+    ("climbing", "Альпінізм", "Climbing"),
+    ("picnic", "Пікнік", "Picnic"),
+    ("other", "Інший", "Other"),
+];
+
+const TOURISM_TYPES: OptionSet = &[
+    ("tent", "Намет", "Tent"),
+    ("sleeping_bag", "Спальний мішок", "Sleeping bag"),
+    ("burner", "Пальник", "Burner"),
+    ("backpack", "Рюкзак", "Backpack"),
+    ("sleeping_pad", "Каремат", "Sleeping pad"),
+    ("dishes", "Посуд", "Dishes"),
+    ("compass", "Компас", "Compass"),
+    ("other", "Інший", "Other"),
+];
+
+const WATER_SPORTS_TYPES: OptionSet = &[
+    ("goggles", "Водні окуляри та маски", "Goggles and masks"),
+    ("fins", "Ласти", "Fins"),
+    ("boards", "Дошки", "Boards"),
+    ("paddles", "Весла", "Paddles"),
+    ("life_jackets", "Рятувальні жилети", "Life jackets"),
+    ("kayaks", "Байдарки", "Kayaks"),
+    ("pump", "Насос", "Pump"),
+    ("other", "Інший", "Other"),
+];
+
+const CYCLING_TYPES: OptionSet = &[
+    ("bicycle", "Велосипед", "Bicycle"),
+    ("wheels", "Колеса", "Wheels"),
+    ("pump", "Насос", "Pump"),
+    ("helmet", "Шолом", "Helmet"),
+    ("lights", "Ліхтарі", "Lights"),
+    ("other", "Інший", "Other"),
+];
+
+const CLIMBING_TYPES: OptionSet = &[
+    ("climbing_shoes", "Скельники", "Climbing shoes"),
+    ("protection", "Страхування", "Protection"),
+    ("carabiner", "Карабін", "Carabiner"),
+    ("rope", "Мотузка", "Rope"),
+    ("helmet", "Каска", "Helmet"),
+    ("other", "Інший", "Other"),
+];
+
+const PICNIC_TYPES: OptionSet = &[
+    ("plaid", "Плед", "Blanket"),
+    ("dishes", "Посуд", "Dishes"),
+    ("burner", "Пальник", "Burner"),
+    ("other", "Інший", "Other"),
+];
+
+const CHILDREN_TYPES: OptionSet = &[
+    (
+        "clothes",
+        "Одяг (комбінезон, футболки, штани, боді, піжама)",
+        "Clothes (overalls, T-shirts, trousers, bodysuits, pajamas)",
+    ),
+    (
+        "shoes",
+        "Взуття (повсякденне, зимове, гумові чоботи, інше)",
+        "Shoes (everyday, winter, rubber boots, other)",
+    ),
+    (
+        "toys",
+        "Іграшки (м'які, розвиваючі, конструктори, для вулиці, інтерактивні, інші)",
+        "Toys (soft, educational, building sets, outdoor, interactive, other)",
+    ),
+    (
+        "care",
+        "Догляд (підгузки, ванночки, термометри, шампуні, щітки, інші)",
+        "Care (diapers, baby baths, thermometers, shampoos, brushes, other)",
+    ),
+    (
+        "education",
+        "Навчання та творчість (розмальовка, для ліплення, пазли, навчальні зошити, абетка, цифри, інше)",
+        "Learning and creativity (coloring books, modeling, puzzles, workbooks, alphabet, numbers, other)",
+    ),
+    ("other", "Інший", "Other"),
+];
+
+/// Every set served by `/options/materials`; a product's material may come from any of them.
+const MATERIAL_SETS: &[OptionSet] = &[
+    SHOE_MATERIALS,
+    CLOTHING_MATERIALS,
+    HOME_TYPES,
+    HOME_MATERIALS,
+    BOOK_GENRES,
+    BOOK_BINDING,
+    BOOK_LANGUAGES,
+    GARDEN_TYPES,
+    ELECTRONICS_TYPES,
+    AUTO_TYPES,
+    STATIONERY_TYPES,
+    ACTIVITY_TYPES,
+    TOURISM_TYPES,
+    WATER_SPORTS_TYPES,
+    CYCLING_TYPES,
+    CLIMBING_TYPES,
+    PICNIC_TYPES,
+    CHILDREN_TYPES,
+];
+
+fn option_values(options: OptionSet, locale: Locale) -> Vec<OptionValue> {
+    options
+        .iter()
+        .map(|(value, ukrainian, english)| OptionValue {
+            value: value.to_string(),
+            label: match locale {
+                Locale::Ukrainian => ukrainian,
+                Locale::English => english,
+            }
+            .to_string(),
+        })
+        .collect()
+}
+
+fn validate_option(field: &str, value: Option<&str>, sets: &[OptionSet]) -> Result<(), AppError> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+
+    let known = sets
+        .iter()
+        .any(|options| options.iter().any(|(known, _, _)| *known == value));
+
+    if known {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Invalid {}: {}",
+            field, value
+        )))
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(LocaleQuery),
+    responses((status = 200, description = "Colors", body = Vec<OptionValue>))
+)]
+#[get("/options/colors")]
+pub async fn get_colors(locale: Locale) -> impl Responder {
+    localized(locale, option_values(COLORS, locale))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(LocaleQuery),
+    responses((status = 200, description = "Shoe sizes", body = Vec<OptionValue>))
+)]
+#[get("/options/shoe-sizes")]
+pub async fn get_shoe_sizes(locale: Locale) -> impl Responder {
+    localized(locale, option_values(SHOE_SIZES, locale))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(LocaleQuery),
+    responses((status = 200, description = "Clothing sizes", body = Vec<OptionValue>))
+)]
+#[get("/options/clothing-sizes")]
+pub async fn get_clothing_sizes(locale: Locale) -> impl Responder {
+    localized(locale, option_values(CLOTHING_SIZES, locale))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(LocaleQuery),
+    responses((status = 200, description = "Genders", body = Vec<OptionValue>))
+)]
+#[get("/options/genders")]
+pub async fn get_genders(locale: Locale) -> impl Responder {
+    localized(locale, option_values(GENDERS, locale))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProductCharacteristics {
+    pub shoe_materials: Vec<OptionValue>,
+    pub clothing_materials: Vec<OptionValue>,
+    pub home_types: Vec<OptionValue>,
+    pub home_materials: Vec<OptionValue>,
+    pub book_genres: Vec<OptionValue>,
+    pub book_binding: Vec<OptionValue>,
+    pub book_languages: Vec<OptionValue>,
+    pub garden_types: Vec<OptionValue>,
+    pub electronics_types: Vec<OptionValue>,
+    pub auto_types: Vec<OptionValue>,
+    pub stationery_types: Vec<OptionValue>,
+    pub activity_types: Vec<OptionValue>,
+    pub tourism_types: Vec<OptionValue>,
+    pub water_sports_types: Vec<OptionValue>,
+    pub cycling_types: Vec<OptionValue>,
+    pub climbing_types: Vec<OptionValue>,
+    pub picnic_types: Vec<OptionValue>,
+    pub children_types: Vec<OptionValue>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Products",
+    params(LocaleQuery),
+    responses((status = 200, description = "Materials and types per category", body = ProductCharacteristics))
+)]
+#[get("/options/materials")]
+pub async fn get_materials(locale: Locale) -> impl Responder {
+    let data = ProductCharacteristics {
+        shoe_materials: option_values(SHOE_MATERIALS, locale),
+        clothing_materials: option_values(CLOTHING_MATERIALS, locale),
+        home_types: option_values(HOME_TYPES, locale),
+        home_materials: option_values(HOME_MATERIALS, locale),
+        book_genres: option_values(BOOK_GENRES, locale),
+        book_binding: option_values(BOOK_BINDING, locale),
+        book_languages: option_values(BOOK_LANGUAGES, locale),
+        garden_types: option_values(GARDEN_TYPES, locale),
+        electronics_types: option_values(ELECTRONICS_TYPES, locale),
+        auto_types: option_values(AUTO_TYPES, locale),
+        stationery_types: option_values(STATIONERY_TYPES, locale),
+        activity_types: option_values(ACTIVITY_TYPES, locale),
+        tourism_types: option_values(TOURISM_TYPES, locale),
+        water_sports_types: option_values(WATER_SPORTS_TYPES, locale),
+        cycling_types: option_values(CYCLING_TYPES, locale),
+        climbing_types: option_values(CLIMBING_TYPES, locale),
+        picnic_types: option_values(PICNIC_TYPES, locale),
+        children_types: option_values(CHILDREN_TYPES, locale),
+    };
+    localized(locale, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> ProductQuery {
+        web::Query::<ProductQuery>::from_query(query)
+            .unwrap()
+            .into_inner()
+    }
+
+    fn public(query: &str) -> BoundSql {
+        products_query(&parse(query), ProductScope::Public { viewer: None }).unwrap()
+    }
+
+    /// The SQL after the shared SELECT, which is the part the filters change.
+    fn filters(sql: &BoundSql) -> &str {
+        sql.sql.strip_prefix(PRODUCT_SELECT).unwrap()
+    }
+
+    fn text_array(values: &[&str]) -> Bind {
+        Bind::TextArray(values.iter().map(|value| value.to_string()).collect())
+    }
+
+    #[test]
+    fn lists_active_products_newest_first_by_default() {
+        let sql = public("");
+
+        assert_eq!(
+            filters(&sql),
+            " WHERE 1=1 AND p.status = ANY($1) GROUP BY p.id ORDER BY p.id DESC LIMIT $2"
+        );
+        assert_eq!(
+            sql.binds,
+            vec![text_array(&["active"]), Bind::Int(DEFAULT_PRODUCTS_LIMIT)]
+        );
+    }
+
+    #[test]
+    fn include_sold_adds_the_sold_status() {
+        let sql = public("include_sold=true");
+
+        assert_eq!(sql.binds[0], text_array(&["active", "sold"]));
+    }
+
+    #[test]
+    fn drafts_are_only_listed_for_their_owner() {
+        let owner = Uuid::new_v4();
+        let query = parse(&format!("user_id={}", owner));
+
+        let own = products_query(
+            &query,
+            ProductScope::Public {
+                viewer: Some(owner),
             },
-            OptionValue {
-                value: "other".into(),
-                label: "Інший".into(),
+        )
+        .unwrap();
+        assert_eq!(own.binds[0], text_array(&["active", "draft"]));
+        assert_eq!(own.binds[1], Bind::Uuid(owner));
+
+        let other = products_query(
+            &query,
+            ProductScope::Public {
+                viewer: Some(Uuid::new_v4()),
             },
-        ],
-    };
+        )
+        .unwrap();
+        assert_eq!(other.binds[0], text_array(&["active"]));
+    }
+
+    #[test]
+    fn owner_scope_lists_every_status() {
+        let owner = Uuid::new_v4();
+        let sql = products_query(&parse(""), ProductScope::Owner(owner)).unwrap();
+
+        assert_eq!(
+            filters(&sql),
+            " WHERE 1=1 AND p.user_id = $1 GROUP BY p.id ORDER BY p.id DESC LIMIT $2"
+        );
+        assert_eq!(sql.binds[0], Bind::Uuid(owner));
+
+        for query in [&format!("user_id={}", Uuid::new_v4()), "include_sold=true"] {
+            assert!(products_query(&parse(query), ProductScope::Owner(owner)).is_err());
+        }
+    }
+
+    #[test]
+    fn keyset_and_offset_paging() {
+        let sql = public("last_seen_id=42&limit=500&offset=10");
+
+        assert!(filters(&sql).contains(" AND p.id < $2"));
+        assert!(filters(&sql).ends_with(" LIMIT $3 OFFSET $4"));
+        assert_eq!(
+            sql.binds[1..],
+            [Bind::Int(42), Bind::Int(MAX_PRODUCTS_LIMIT), Bind::Int(10)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_paging() {
+        let scope = || ProductScope::Public { viewer: None };
+
+        assert!(products_query(&parse("limit=0"), scope()).is_err());
+        assert!(products_query(&parse("offset=-1"), scope()).is_err());
+    }
+
+    #[test]
+    fn option_filters_bind_validated_values() {
+        let sql = public("color=red,%20blue&gender=male");
+
+        assert!(filters(&sql).contains(" AND p.color = ANY($2)"));
+        assert!(filters(&sql).contains(" AND p.gender = $3"));
+        assert_eq!(sql.binds[1], text_array(&["red", "blue"]));
+        assert_eq!(sql.binds[2], Bind::Text("male".into()));
+    }
+
+    #[test]
+    fn rejects_unknown_option_values() {
+        let scope = || ProductScope::Public { viewer: None };
 
-    HttpResponse::Ok().json(data)
+        assert!(products_query(&parse("color=red,plaid"), scope()).is_err());
+        assert!(products_query(&parse("gender=robot"), scope()).is_err());
+    }
+
+    #[test]
+    fn price_ranges_need_a_currency() {
+        let scope = || ProductScope::Public { viewer: None };
+
+        assert!(products_query(&parse("min_price=10"), scope()).is_err());
+        assert!(products_query(&parse("currency=UAH&min_price=20&max_price=10"), scope()).is_err());
+
+        let sql = public("currency=EUR&min_price=10&max_price=20");
+        assert!(filters(&sql).contains(" AND p.currency = $2 AND p.price >= $3 AND p.price <= $4"));
+        assert_eq!(
+            sql.binds[1..4],
+            [
+                Bind::Text("EUR".into()),
+                Bind::Float(10.0),
+                Bind::Float(20.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn substring_search_matches_title_and_description() {
+        let sql = public("search=boots");
+
+        assert!(filters(&sql).contains(" AND (p.title ILIKE $2 OR p.description ILIKE $3)"));
+        assert!(filters(&sql).ends_with(" ORDER BY p.id DESC LIMIT $4"));
+        assert_eq!(sql.binds[1], Bind::Text("%boots%".into()));
+        assert_eq!(sql.binds[2], Bind::Text("%boots%".into()));
+
+        let sql = public("search=50%25_off");
+        assert_eq!(sql.binds[1], Bind::Text("%50\\%\\_off%".into()));
+        assert_eq!(sql.binds[2], Bind::Text("%50\\%\\_off%".into()));
+    }
+
+    #[test]
+    fn fulltext_search_orders_by_rank() {
+        let sql = public("search=boots&search_mode=fulltext");
+
+        assert!(filters(&sql).contains(" AND p.search_vector @@ plainto_tsquery('simple', $2)"));
+        assert!(filters(&sql).contains(
+            " ORDER BY ts_rank(p.search_vector, plainto_tsquery('simple', $3)) DESC, p.id DESC"
+        ));
+        assert_eq!(sql.binds[1], Bind::Text("boots".into()));
+        assert_eq!(sql.binds[2], Bind::Text("boots".into()));
+    }
+
+    #[test]
+    fn keyset_paging_is_rejected_unless_ordered_by_id() {
+        let scope = || ProductScope::Public { viewer: None };
+
+        for query in [
+            "search=boots&search_mode=fulltext&last_seen_id=10",
+            "lat=50.45&lon=30.52&last_seen_id=10",
+        ] {
+            assert!(products_query(&parse(query), scope()).is_err());
+        }
+        assert!(products_query(&parse("search_mode=fulltext&last_seen_id=10"), scope()).is_ok());
+        assert!(products_query(&parse("search=boots&last_seen_id=10"), scope()).is_ok());
+    }
+
+    #[test]
+    fn a_point_orders_by_distance_and_a_radius_filters_by_it() {
+        let sql = public("lat=50.45&lon=30.52&radius_km=10");
+
+        assert!(filters(&sql).contains(" AND p.latitude IS NOT NULL AND (2 * $2 "));
+        assert!(filters(&sql).contains(" <= $6 GROUP BY p.id ORDER BY (2 * $7 "));
+        assert!(filters(&sql).contains(" ASC NULLS LAST, p.id DESC LIMIT $11"));
+        assert_eq!(sql.binds[5], Bind::Float(10.0));
+    }
+
+    #[test]
+    fn rejects_incomplete_locations() {
+        let scope = || ProductScope::Public { viewer: None };
+
+        assert!(products_query(&parse("lat=50.45"), scope()).is_err());
+        assert!(products_query(&parse("radius_km=10"), scope()).is_err());
+        assert!(products_query(&parse("lat=50.45&lon=30.52&radius_km=0"), scope()).is_err());
+    }
+
+    #[test]
+    fn category_and_condition_filters() {
+        let sql = public("category=3&condition=USED");
+
+        assert!(filters(&sql).contains(" AND p.category_id = $2 AND p.condition = $3"));
+        assert_eq!(sql.binds[1..3], [Bind::Int(3), Bind::Text("USED".into())]);
+        assert!(web::Query::<ProductQuery>::from_query("category=shoes").is_err());
+    }
+
+    #[test]
+    fn sellers_only_requires_the_seller_role() {
+        assert!(!filters(&public("sellers_only=false")).contains("sellers"));
+        assert!(filters(&public("sellers_only=true")).contains(
+            " AND EXISTS (SELECT 1 FROM sellers s WHERE s.user_id = p.user_id) GROUP BY"
+        ));
+    }
+
+    #[test]
+    fn counts_use_the_listing_filters_without_paging() {
+        let query =
+            parse("category=3&search=boots&currency=UAH&max_price=500&last_seen_id=9&limit=5");
+        let listing = products_query(&query, ProductScope::Public { viewer: None }).unwrap();
+        let count = count_query(&query, ProductScope::Public { viewer: None }).unwrap();
+
+        let (where_clause, _) = filters(&listing).split_once(" AND p.id < ").unwrap();
+        assert_eq!(
+            count.sql,
+            format!("SELECT COUNT(*) FROM products p{}", where_clause)
+        );
+        assert_eq!(count.binds, listing.binds[..count.binds.len()]);
+    }
+
+    #[test]
+    fn etag_changes_with_the_body_and_answers_if_none_match() {
+        let first = json_with_etag(
+            &actix_web::test::TestRequest::default().to_http_request(),
+            &[1, 2],
+        )
+        .unwrap();
+        let etag = first.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(first.status(), 200);
+
+        let same = actix_web::test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_http_request();
+        let unchanged = json_with_etag(&same, &[1, 2]).unwrap();
+        assert_eq!(unchanged.status(), 304);
+        assert_eq!(unchanged.headers().get(header::ETAG), Some(&etag));
+
+        for response in [&first, &unchanged] {
+            let headers = response.headers();
+            assert_eq!(headers.get(header::VARY).unwrap(), "Authorization");
+            assert_eq!(
+                headers.get(header::CACHE_CONTROL).unwrap(),
+                "private, no-cache"
+            );
+        }
+
+        let changed = json_with_etag(&same, &[1, 3]).unwrap();
+        assert_eq!(changed.status(), 200);
+        assert_ne!(changed.headers().get(header::ETAG), Some(&etag));
+    }
+
+    #[test]
+    fn placeholders_match_the_bound_values() {
+        let sql = public(
+            "category=3&color=red&city=Kyiv&search=boots&lat=50.45&lon=30.52&radius_km=5&offset=20",
+        );
+
+        for n in 1..=sql.binds.len() {
+            assert!(sql.sql.contains(&format!("${}", n)), "${} is missing", n);
+        }
+        assert!(!sql.sql.contains(&format!("${}", sql.binds.len() + 1)));
+    }
+
+    fn locale(uri: &str, accept_language: Option<&str>) -> Locale {
+        let mut req = actix_web::test::TestRequest::with_uri(uri);
+        if let Some(accept_language) = accept_language {
+            req = req.insert_header((header::ACCEPT_LANGUAGE, accept_language));
+        }
+        let (req, mut payload) = req.to_http_parts();
+
+        Locale::from_request(&req, &mut payload)
+            .into_inner()
+            .unwrap()
+    }
+
+    #[test]
+    fn locale_follows_the_best_supported_accept_language() {
+        assert_eq!(locale("/", None), Locale::Ukrainian);
+        assert_eq!(locale("/", Some("en-GB")), Locale::English);
+        assert_eq!(
+            locale("/", Some("fr;q=0.9, uk;q=0.5, en;q=0.7")),
+            Locale::English
+        );
+        assert_eq!(locale("/", Some("fr, de")), Locale::Ukrainian);
+    }
+
+    #[test]
+    fn lang_parameter_wins_over_accept_language() {
+        assert_eq!(locale("/?lang=uk", Some("en")), Locale::Ukrainian);
+        assert_eq!(locale("/?lang=EN", None), Locale::English);
+        assert_eq!(locale("/?lang=fr", Some("en")), Locale::English);
+    }
+
+    #[test]
+    fn brands_are_normalized_and_matched_ignoring_case() {
+        assert_eq!(
+            normalize_brand("  New \t Balance "),
+            Some("New Balance".into())
+        );
+        assert_eq!(normalize_brand("   "), None);
+
+        let sql = public("brand=Nike,%20new%20%20balance%20,");
+        assert!(filters(&sql).contains(" AND lower(p.brand) = ANY($2)"));
+        assert_eq!(sql.binds[1], text_array(&["nike", "new balance"]));
+    }
+
+    #[test]
+    fn escape_like_keeps_wildcards_literal() {
+        assert_eq!(escape_like("50% off_sale"), "50\\% off\\_sale");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+        assert_eq!(escape_like("кеди"), "кеди");
+    }
+
+    #[test]
+    fn prices_must_fit_the_price_column() {
+        assert!(validate_price(0.0).is_ok());
+        assert!(validate_price(MAX_PRICE).is_ok());
+        assert!(validate_price(-0.01).is_err());
+        assert!(validate_price(1e10).is_err());
+        assert!(validate_price(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn unknown_parameters_are_rejected() {
+        assert!(web::Query::<ProductQuery>::from_query("catgory=1").is_err());
+    }
 }
@@ -0,0 +1,216 @@
+use crate::errors::{AppError, ErrorEnvelope};
+use crate::handlers::auth::AuthenticatedUser;
+use actix_web::{HttpResponse, Responder, post, web};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// How long a user has to wait before reporting the same target again.
+const REPORT_WINDOW_DAYS: i32 = 30;
+const MAX_REPORT_COMMENT_LENGTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportReason {
+    Spam,
+    Fraud,
+    Prohibited,
+    Offensive,
+    Other,
+}
+
+impl fmt::Display for ReportReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReportReason::Spam => write!(f, "spam"),
+            ReportReason::Fraud => write!(f, "fraud"),
+            ReportReason::Prohibited => write!(f, "prohibited"),
+            ReportReason::Offensive => write!(f, "offensive"),
+            ReportReason::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReportRequest {
+    reason: ReportReason,
+    comment: Option<String>,
+}
+
+impl ReportRequest {
+    fn comment(&self) -> Result<Option<&str>, AppError> {
+        let comment = self
+            .comment
+            .as_deref()
+            .map(str::trim)
+            .filter(|comment| !comment.is_empty());
+
+        if comment.is_some_and(|comment| comment.chars().count() > MAX_REPORT_COMMENT_LENGTH) {
+            return Err(AppError::BadRequest(format!(
+                "Comment must be at most {} characters",
+                MAX_REPORT_COMMENT_LENGTH
+            )));
+        }
+
+        Ok(comment)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReportResponse {
+    id: i64,
+}
+
+/// Which column of `reports` holds the target.
+enum ReportTarget {
+    Product(i32),
+    User(Uuid),
+}
+
+/// Stores the report unless `reporter_id` already reported the same target within
+/// `REPORT_WINDOW_DAYS`, in which case `None` is returned.
+async fn insert_report(
+    db_pool: &PgPool,
+    reporter_id: Uuid,
+    target: ReportTarget,
+    req: &ReportRequest,
+) -> Result<Option<i64>, AppError> {
+    let (product_id, user_id) = match target {
+        ReportTarget::Product(product_id) => (Some(product_id), None),
+        ReportTarget::User(user_id) => (None, Some(user_id)),
+    };
+
+    sqlx::query_scalar(
+        "INSERT INTO reports (reporter_id, product_id, user_id, reason, comment)
+         SELECT $1, $2, $3, $4, $5
+         WHERE NOT EXISTS (
+             SELECT 1 FROM reports
+             WHERE reporter_id = $1
+               AND product_id IS NOT DISTINCT FROM $2
+               AND user_id IS NOT DISTINCT FROM $3
+               AND created_at > NOW() - make_interval(days => $6)
+         )
+         RETURNING id",
+    )
+    .bind(reporter_id)
+    .bind(product_id)
+    .bind(user_id)
+    .bind(req.reason.to_string())
+    .bind(req.comment()?)
+    .bind(REPORT_WINDOW_DAYS)
+    .fetch_optional(db_pool)
+    .await
+    .map_err(AppError::internal)
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/products",
+    tag = "Reports",
+    params(("id" = i32, Path, description = "Product id")),
+    request_body = ReportRequest,
+    responses(
+        (status = 201, description = "Report stored", body = ReportResponse),
+        (status = 400, description = "Own product or comment too long", body = ErrorEnvelope),
+        (status = 404, description = "Product not found", body = ErrorEnvelope),
+        (status = 409, description = "Product already reported recently", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/{id}/report")]
+pub async fn report_product(
+    user: AuthenticatedUser,
+    path: web::Path<i32>,
+    req: web::Json<ReportRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let product_id = path.into_inner();
+
+    // Someone else's draft is reported as missing, as in `get_product_by_id`.
+    let owner: Option<Uuid> = sqlx::query_scalar(
+        "SELECT user_id FROM products WHERE id = $1 AND (status <> 'draft' OR user_id = $2)",
+    )
+    .bind(product_id)
+    .bind(user.0.sub)
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    match owner {
+        None => return Err(AppError::NotFound("Product not found".into())),
+        Some(owner) if owner == user.0.sub => {
+            return Err(AppError::BadRequest(
+                "You cannot report your own product".into(),
+            ));
+        }
+        Some(_) => {}
+    }
+
+    let Some(id) = insert_report(
+        db_pool.get_ref(),
+        user.0.sub,
+        ReportTarget::Product(product_id),
+        &req,
+    )
+    .await?
+    else {
+        return Err(AppError::Conflict(
+            "You have already reported this product".into(),
+        ));
+    };
+
+    Ok(HttpResponse::Created().json(ReportResponse { id }))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/users",
+    tag = "Reports",
+    params(("id" = Uuid, Path, description = "User id")),
+    request_body = ReportRequest,
+    responses(
+        (status = 201, description = "Report stored", body = ReportResponse),
+        (status = 400, description = "Reporting yourself or comment too long", body = ErrorEnvelope),
+        (status = 404, description = "User not found", body = ErrorEnvelope),
+        (status = 409, description = "User already reported recently", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/{id}/report")]
+pub async fn report_user(
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<ReportRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let user_id = path.into_inner();
+
+    if user_id == user.0.sub {
+        return Err(AppError::BadRequest("You cannot report yourself".into()));
+    }
+
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    if exists.is_none() {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    let Some(id) = insert_report(
+        db_pool.get_ref(),
+        user.0.sub,
+        ReportTarget::User(user_id),
+        &req,
+    )
+    .await?
+    else {
+        return Err(AppError::Conflict(
+            "You have already reported this user".into(),
+        ));
+    };
+
+    Ok(HttpResponse::Created().json(ReportResponse { id }))
+}
@@ -1,7 +1,17 @@
-use crate::handlers::auth::AuthenticatedUser;
-use actix_web::{HttpResponse, Responder, post, web};
-use serde::Deserialize;
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use crate::errors::{AppError, ErrorEnvelope};
+use crate::handlers::auth::{AuthenticatedUser, TokenClaims, normalize_email};
+use crate::handlers::products::{
+    ProductQuery, ProductScope, delete_unreferenced_uploads, fetch_products,
+};
+use crate::services::email::send_email_change_email;
+use crate::services::token::{JwtConfig, TokenInput, TokenType};
+use actix_web::{HttpResponse, Responder, delete, get, patch, post, web};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 #[derive(Deserialize)]
@@ -10,27 +20,21 @@ pub struct CreateRequest {
     is_seller: bool,
 }
 
-async fn update_user_role(
-    db_pool: &PgPool,
-    user_id: &Uuid,
-    table: &str,
-) -> Result<(), actix_web::Error> {
+async fn update_user_role(db_pool: &PgPool, user_id: &Uuid, table: &str) -> Result<(), AppError> {
     sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
         .bind(user_id)
         .execute(db_pool)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     let result = sqlx::query(&format!("INSERT INTO {} (user_id) VALUES ($1)", table))
         .bind(user_id)
         .execute(db_pool)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     if result.rows_affected() == 0 {
-        return Err(actix_web::error::ErrorInternalServerError(
-            "Failed to insert role",
-        ));
+        return Err(AppError::internal("Failed to insert role"));
     }
 
     Ok(())
@@ -41,7 +45,7 @@ async fn create(
     user: AuthenticatedUser,
     req: web::Json<CreateRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user_id = &user.0.sub;
 
     if req.is_buyer {
@@ -70,14 +74,14 @@ async fn categories(
     user: AuthenticatedUser,
     req: web::Json<CategoriesRequest>,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user_id = &user.0.sub;
 
     sqlx::query("DELETE FROM user_categories WHERE user_id = $1")
         .bind(user_id)
         .execute(db_pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     if req.categories.is_empty() {
         return Ok(HttpResponse::Ok().body("User categories cleared"));
@@ -94,7 +98,480 @@ async fn categories(
         .build()
         .execute(db_pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(AppError::internal)?;
 
     Ok(HttpResponse::Ok().body("User categories updated successfully"))
 }
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    email: String,
+}
+
+#[patch("/email")]
+async fn change_email(
+    user: AuthenticatedUser,
+    req: web::Json<ChangeEmailRequest>,
+    db_pool: web::Data<PgPool>,
+    jwt: web::Data<JwtConfig>,
+) -> Result<impl Responder, AppError> {
+    let user_id = user.0.sub;
+    let email = normalize_email(&req.email)?;
+
+    let existing_user: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    if existing_user.is_some() {
+        return Err(AppError::Conflict(
+            "User with this email already exists".into(),
+        ));
+    }
+
+    // The pending address is only committed once the confirmation email went out,
+    // so a failed send doesn't leave an address behind that can't be confirmed.
+    let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+    // The current email stays active until the new one is confirmed from the link.
+    let first_name: String = sqlx::query_scalar(
+        "UPDATE users SET pending_email = $1 WHERE id = $2 RETURNING first_name",
+    )
+    .bind(&email)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    let token = jwt.create_token(TokenInput {
+        user_id,
+        email: &email,
+        token_type: TokenType::EmailChange,
+        is_admin: false,
+    })?;
+
+    send_email_change_email(&email, &first_name, &token)
+        .await
+        .map_err(AppError::internal)?;
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Confirmation email sent to the new address"
+    })))
+}
+
+#[delete("/me")]
+async fn delete_me(
+    claims: TokenClaims,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let user_id = &claims.0.sub;
+
+    let mut tx = db_pool.begin().await.map_err(AppError::internal)?;
+
+    // `TokenClaims` doesn't require the user row, so a retry after the account is
+    // already gone succeeds instead of failing authentication.
+    let active: Option<bool> =
+        sqlx::query_scalar("SELECT active FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+
+    match active {
+        None => return Ok(HttpResponse::Ok().body("Account deleted")),
+        Some(false) => {
+            return Err(AppError::Unauthorized("User not found or inactive".into()));
+        }
+        Some(true) => {}
+    }
+
+    let upload_urls: Vec<String> = sqlx::query_scalar(
+        "SELECT pi.url FROM product_images pi
+         JOIN products p ON p.id = pi.product_id
+         WHERE p.user_id = $1
+         UNION ALL
+         SELECT pv.url FROM product_videos pv
+         JOIN products p ON p.id = pv.product_id
+         WHERE p.user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::internal)?;
+
+    let statements = [
+        "DELETE FROM idempotency_keys WHERE user_id = $1",
+        "DELETE FROM reports WHERE reporter_id = $1 OR user_id = $1",
+        "DELETE FROM product_images WHERE product_id IN (SELECT id FROM products WHERE user_id = $1)",
+        "DELETE FROM product_videos WHERE product_id IN (SELECT id FROM products WHERE user_id = $1)",
+        "DELETE FROM product_delivery_options WHERE product_id IN (SELECT id FROM products WHERE user_id = $1)",
+        "DELETE FROM product_payment_options WHERE product_id IN (SELECT id FROM products WHERE user_id = $1)",
+        "DELETE FROM products WHERE user_id = $1",
+        "DELETE FROM buyers WHERE user_id = $1",
+        "DELETE FROM sellers WHERE user_id = $1",
+        "DELETE FROM user_categories WHERE user_id = $1",
+        "DELETE FROM refresh_tokens WHERE user_id = $1",
+        "DELETE FROM otp_tokens WHERE user_id = $1",
+        "DELETE FROM blocked_users WHERE blocker_id = $1 OR blocked_id = $1",
+        "DELETE FROM reviews WHERE reviewer_id = $1 OR seller_id = $1",
+        "DELETE FROM users WHERE id = $1",
+    ];
+
+    for statement in statements {
+        sqlx::query(statement)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::internal)?;
+    }
+
+    tx.commit().await.map_err(AppError::internal)?;
+
+    // The account is already gone at this point, so leftover objects are only logged.
+    delete_unreferenced_uploads(db_pool.get_ref(), upload_urls).await;
+
+    Ok(HttpResponse::Ok().body("Account deleted"))
+}
+
+/// Blocks a user. A blocked user can no longer leave a review for the blocker.
+#[post("/{id}/block")]
+async fn block_user(
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let blocker_id = user.0.sub;
+    let blocked_id = path.into_inner();
+
+    if blocker_id == blocked_id {
+        return Err(AppError::BadRequest("You cannot block yourself".into()));
+    }
+
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        .bind(blocked_id)
+        .fetch_optional(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    if exists.is_none() {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    sqlx::query(
+        "INSERT INTO blocked_users (blocker_id, blocked_id) VALUES ($1, $2)
+         ON CONFLICT (blocker_id, blocked_id) DO NOTHING",
+    )
+    .bind(blocker_id)
+    .bind(blocked_id)
+    .execute(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().body("User blocked"))
+}
+
+#[delete("/{id}/block")]
+async fn unblock_user(
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    sqlx::query("DELETE FROM blocked_users WHERE blocker_id = $1 AND blocked_id = $2")
+        .bind(user.0.sub)
+        .bind(path.into_inner())
+        .execute(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().body("User unblocked"))
+}
+
+const MIN_RATING: i16 = 1;
+const MAX_RATING: i16 = 5;
+
+#[derive(Serialize)]
+struct RatingSummary {
+    average_rating: Option<f64>,
+    review_count: i64,
+}
+
+async fn rating_summary(db_pool: &PgPool, seller_id: Uuid) -> Result<RatingSummary, AppError> {
+    let (average_rating, review_count): (Option<f64>, i64) =
+        sqlx::query_as("SELECT AVG(rating)::float8, COUNT(*) FROM reviews WHERE seller_id = $1")
+            .bind(seller_id)
+            .fetch_one(db_pool)
+            .await
+            .map_err(AppError::internal)?;
+
+    Ok(RatingSummary {
+        average_rating,
+        review_count,
+    })
+}
+
+#[derive(FromRow)]
+struct ProfileRow {
+    id: Uuid,
+    first_name: String,
+    last_name: String,
+    email: String,
+    last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ProfileResponse {
+    id: Uuid,
+    first_name: String,
+    last_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    /// Time of the user's last authenticated request, to the minute.
+    last_seen_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    rating: RatingSummary,
+}
+
+async fn profile(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    include_private: bool,
+) -> Result<ProfileResponse, AppError> {
+    let row = sqlx::query_as::<_, ProfileRow>(
+        "SELECT id, first_name, last_name, email, last_seen_at
+         FROM users WHERE id = $1 AND active = true",
+    )
+    .bind(user_id)
+    .fetch_optional(db_pool)
+    .await
+    .map_err(AppError::internal)?
+    .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+    Ok(ProfileResponse {
+        id: row.id,
+        first_name: row.first_name,
+        last_name: row.last_name,
+        email: include_private.then_some(row.email),
+        last_seen_at: row.last_seen_at,
+        rating: rating_summary(db_pool, user_id).await?,
+    })
+}
+
+#[get("/me")]
+async fn get_me(
+    user: AuthenticatedUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let profile = profile(db_pool.get_ref(), user.0.sub, true).await?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// The caller's own listings, including drafts, sold and archived products.
+/// Accepts the same filters as the public product list, except `user_id` and
+/// `include_sold`.
+#[get("/me/products")]
+async fn get_my_products(
+    user: AuthenticatedUser,
+    query: web::Query<ProductQuery>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let products =
+        fetch_products(db_pool.get_ref(), &query, ProductScope::Owner(user.0.sub)).await?;
+
+    Ok(HttpResponse::Ok().json(products))
+}
+
+#[get("/{id}")]
+async fn get_user(
+    path: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let profile = profile(db_pool.get_ref(), path.into_inner(), false).await?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateReviewRequest {
+    rating: i16,
+    comment: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateReviewResponse {
+    id: i32,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1/users",
+    tag = "Users",
+    params(("id" = Uuid, Path, description = "Seller id")),
+    request_body = CreateReviewRequest,
+    responses(
+        (status = 201, description = "Review stored", body = CreateReviewResponse),
+        (status = 400, description = "Reviewing yourself or rating out of range", body = ErrorEnvelope),
+        (status = 403, description = "The seller has blocked you", body = ErrorEnvelope),
+        (status = 404, description = "Seller not found", body = ErrorEnvelope),
+        (status = 409, description = "Seller already reviewed", body = ErrorEnvelope)
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/{id}/reviews")]
+pub async fn create_review(
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<CreateReviewRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let reviewer_id = user.0.sub;
+    let seller_id = path.into_inner();
+
+    if reviewer_id == seller_id {
+        return Err(AppError::BadRequest("You cannot review yourself".into()));
+    }
+
+    if !(MIN_RATING..=MAX_RATING).contains(&req.rating) {
+        return Err(AppError::BadRequest(format!(
+            "Rating must be between {} and {}",
+            MIN_RATING, MAX_RATING
+        )));
+    }
+
+    let seller: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        .bind(seller_id)
+        .fetch_optional(db_pool.get_ref())
+        .await
+        .map_err(AppError::internal)?;
+
+    if seller.is_none() {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    let blocked: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM blocked_users WHERE blocker_id = $1 AND blocked_id = $2)",
+    )
+    .bind(seller_id)
+    .bind(reviewer_id)
+    .fetch_one(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    if blocked {
+        return Err(AppError::Forbidden("You cannot review this user".into()));
+    }
+
+    let review_id: Option<i32> = sqlx::query_scalar(
+        "INSERT INTO reviews (reviewer_id, seller_id, rating, comment) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (reviewer_id, seller_id) DO NOTHING
+         RETURNING id",
+    )
+    .bind(reviewer_id)
+    .bind(seller_id)
+    .bind(req.rating)
+    .bind(
+        req.comment
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty()),
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    let Some(review_id) = review_id else {
+        return Err(AppError::Conflict(
+            "You have already reviewed this seller".into(),
+        ));
+    };
+
+    Ok(HttpResponse::Created().json(CreateReviewResponse { id: review_id }))
+}
+
+#[derive(Serialize, FromRow)]
+struct Review {
+    id: i32,
+    reviewer_id: Uuid,
+    rating: i16,
+    comment: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct ReviewsResponse {
+    #[serde(flatten)]
+    rating: RatingSummary,
+    /// Number of reviews per rating, with every rating from 1 to 5 present.
+    rating_distribution: BTreeMap<i16, i64>,
+    reviews: Vec<Review>,
+}
+
+const DEFAULT_REVIEWS_LIMIT: i64 = 20;
+const MAX_REVIEWS_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct ReviewsQuery {
+    /// Only reviews older than this one, i.e. the `id` of the last review of
+    /// the previous page.
+    before_id: Option<i32>,
+    /// Page size, 20 unless set. Values above 100 are treated as 100.
+    limit: Option<i64>,
+}
+
+async fn rating_distribution(
+    db_pool: &PgPool,
+    seller_id: Uuid,
+) -> Result<BTreeMap<i16, i64>, AppError> {
+    let counts: Vec<(i16, i64)> =
+        sqlx::query_as("SELECT rating, COUNT(*) FROM reviews WHERE seller_id = $1 GROUP BY rating")
+            .bind(seller_id)
+            .fetch_all(db_pool)
+            .await
+            .map_err(AppError::internal)?;
+
+    let mut distribution: BTreeMap<i16, i64> = (MIN_RATING..=MAX_RATING)
+        .map(|rating| (rating, 0))
+        .collect();
+    distribution.extend(counts);
+
+    Ok(distribution)
+}
+
+/// A page of the seller's reviews, newest first, with the summary of all of them.
+#[get("/{id}/reviews")]
+async fn get_reviews(
+    path: web::Path<Uuid>,
+    query: web::Query<ReviewsQuery>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder, AppError> {
+    let seller_id = path.into_inner();
+
+    let limit = match query.limit {
+        None => DEFAULT_REVIEWS_LIMIT,
+        Some(limit) if limit <= 0 => {
+            return Err(AppError::BadRequest("limit must be positive".into()));
+        }
+        Some(limit) => limit.min(MAX_REVIEWS_LIMIT),
+    };
+
+    let reviews = sqlx::query_as::<_, Review>(
+        "SELECT id, reviewer_id, rating, comment, created_at FROM reviews
+         WHERE seller_id = $1 AND ($2::int IS NULL OR id < $2)
+         ORDER BY id DESC
+         LIMIT $3",
+    )
+    .bind(seller_id)
+    .bind(query.before_id)
+    .bind(limit)
+    .fetch_all(db_pool.get_ref())
+    .await
+    .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok().json(ReviewsResponse {
+        rating: rating_summary(db_pool.get_ref(), seller_id).await?,
+        rating_distribution: rating_distribution(db_pool.get_ref(), seller_id).await?,
+        reviews,
+    }))
+}
@@ -0,0 +1,5 @@
+pub mod app;
+pub mod errors;
+pub mod handlers;
+pub mod middleware;
+pub mod services;
@@ -1,99 +1,153 @@
-use actix_web::{App, HttpServer, web};
-use dotenv;
+use actix_web::HttpServer;
+use actix_web::web;
+use marketplace_api::app::{AppState, build_app};
+use marketplace_api::handlers::auth::{SignupConfig, delete_expired_otps};
+use marketplace_api::middleware::rate_limit::{RateLimitConfig, RateLimits};
+use marketplace_api::services::images::WEBP_QUALITY;
+use marketplace_api::services::password::PasswordConfig;
+use marketplace_api::services::s3::{
+    ALLOWED_IMAGE_TYPES, MAX_REQUEST_UPLOAD_BYTES, MAX_UPLOAD_BYTES,
+};
+use marketplace_api::services::token::JwtConfig;
+use marketplace_api::services::videos::MAX_VIDEO_BYTES;
+use once_cell::sync::Lazy;
 use sqlx::postgres::PgPoolOptions;
 use std::env;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
 
-mod handlers;
-mod services;
-
-use crate::handlers::auth::{
-    SignupRequest, confirm, login, logout, otp_verify, refresh_token, reset_password, signup,
-    update_password,
-};
-use crate::handlers::products::{
-    categories as product_categories, create as product_create, delivery_options,
-    get_clothing_sizes, get_colors, get_genders, get_materials, get_products, get_shoe_sizes,
-    payment_options,
-};
-use crate::handlers::users::{categories as user_categories, create as user_create};
-use actix_cors::Cors;
-use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
-
-#[derive(OpenApi)]
-#[openapi(
-    paths(
-        crate::handlers::auth::signup,
-    ),
-    components(
-        schemas(SignupRequest)
-    ),
-    tags(
-        (name = "Auth", description = "Register users.")
-    )
-)]
-pub struct ApiDoc;
+/// Reads `name` from the environment, falling back to `default` when unset and
+/// panicking with a clear message when it can't be parsed.
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} has an invalid value: {:?}", name, value)),
+        Err(_) => default,
+    }
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    println!("Starting server");
-
     dotenv::from_filename("env").ok();
 
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+
+    tracing::info!("Starting server");
+
+    let jwt_config = JwtConfig::from_env().unwrap_or_else(|e| panic!("{}", e));
+    let password_config = PasswordConfig::from_env().unwrap_or_else(|e| panic!("{}", e));
+    // Fail at startup rather than on the first upload if these are misconfigured.
+    Lazy::force(&MAX_UPLOAD_BYTES);
+    Lazy::force(&MAX_REQUEST_UPLOAD_BYTES);
+    Lazy::force(&MAX_VIDEO_BYTES);
+    Lazy::force(&ALLOWED_IMAGE_TYPES);
+    Lazy::force(&WEBP_QUALITY);
+
+    let signup_config = SignupConfig {
+        auto_confirm: env_or("AUTO_CONFIRM", false),
+    };
+    if signup_config.auto_confirm {
+        tracing::warn!(
+            "AUTO_CONFIRM is enabled, new accounts are activated without email confirmation"
+        );
+    }
+
+    let dev_mode: bool = env_or("DEV_MODE", false);
+    let allowed_origins: Vec<String> = env::var("ALLOWED_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+
+    if dev_mode {
+        tracing::warn!("DEV_MODE is enabled, CORS accepts any origin");
+    } else if allowed_origins.is_empty() {
+        tracing::warn!("ALLOWED_ORIGINS is empty, cross-origin requests will be rejected");
+    }
+
+    let rate_limits = web::Data::new(RateLimits::new(RateLimitConfig {
+        reads_per_ip: env_or("RATE_LIMIT_READS_PER_MINUTE", NonZeroU32::new(300).unwrap()),
+        writes_per_ip: env_or("RATE_LIMIT_WRITES_PER_MINUTE", NonZeroU32::new(60).unwrap()),
+        writes_per_user: env_or(
+            "RATE_LIMIT_USER_WRITES_PER_MINUTE",
+            NonZeroU32::new(30).unwrap(),
+        ),
+        trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", false),
+    }));
+
+    let cleanup_limits = rate_limits.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            cleanup_limits.retain_recent();
+        }
+    });
+
+    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
+    let port: u16 = env_or("PORT", 4000);
+
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let pool = PgPoolOptions::new()
-        .max_connections(5) // Максимальна кількість з'єднань
+        .max_connections(env_or("DB_MAX_CONNECTIONS", 5)) // Максимальна кількість з'єднань
+        .acquire_timeout(Duration::from_secs(env_or("DB_ACQUIRE_TIMEOUT", 30)))
+        .idle_timeout(Duration::from_secs(env_or("DB_IDLE_TIMEOUT", 600)))
         .connect(&database_url)
         .await
         .expect("Failed to create pool.");
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(
-                Cors::default()
-                    .allow_any_origin() // або .allowed_origin("https://твій-домен")
-                    .allow_any_method()
-                    .allow_any_header(),
-            )
-            .app_data(web::Data::new(pool.clone()))
-            .service(
-                SwaggerUi::new("/swagger-ui/{_:.*}")
-                    .url("/api-doc/openapi.json", ApiDoc::openapi()),
-            )
-            .service(
-                web::scope("/api/v1")
-                    .service(
-                        web::scope("/auth")
-                            .service(signup)
-                            .service(confirm)
-                            .service(login)
-                            .service(logout)
-                            .service(refresh_token)
-                            .service(reset_password)
-                            .service(otp_verify)
-                            .service(update_password),
-                    )
-                    .service(
-                        web::scope("/users")
-                            .service(user_create)
-                            .service(user_categories),
-                    )
-                    .service(
-                        web::scope("/products")
-                            .service(product_categories)
-                            .service(payment_options)
-                            .service(delivery_options)
-                            .service(product_create)
-                            .service(get_products)
-                            .service(get_colors)
-                            .service(get_shoe_sizes)
-                            .service(get_clothing_sizes)
-                            .service(get_genders)
-                            .service(get_materials),
-                    ),
-            )
-    })
-    .bind(("0.0.0.0", 4000))?
-    .run()
-    .await
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .expect("Failed to run database migrations.");
+
+    let cleanup_pool = pool.clone();
+    let otp_cleanup_interval = Duration::from_secs(
+        env_or("OTP_CLEANUP_INTERVAL", NonZeroU64::new(60 * 60).unwrap()).get(),
+    );
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(otp_cleanup_interval);
+        loop {
+            interval.tick().await;
+            match delete_expired_otps(&cleanup_pool).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!(deleted, "Deleted expired OTPs"),
+                Err(e) => tracing::warn!(error = %e, "Failed to delete expired OTPs"),
+            }
+        }
+    });
+
+    let shutdown_timeout: u64 = env_or("SHUTDOWN_TIMEOUT", 30);
+    let shutdown_pool = pool.clone();
+    let state = AppState {
+        pool,
+        jwt_config,
+        password_config,
+        signup_config,
+        rate_limits,
+        dev_mode,
+        allowed_origins,
+    };
+
+    // actix stops accepting connections on SIGTERM/SIGINT and gives in-flight
+    // requests `shutdown_timeout` seconds to finish before `run()` returns.
+    let result = HttpServer::new(move || build_app(state.clone()))
+        .bind((host, port))?
+        .shutdown_timeout(shutdown_timeout)
+        .run()
+        .await;
+
+    tracing::info!("Server stopped, closing database pool");
+    shutdown_pool.close().await;
+
+    result
 }
@@ -0,0 +1,118 @@
+use crate::errors::AppError;
+use crate::handlers::auth::AuthenticatedUser;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{Error, web};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota};
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use uuid::Uuid;
+
+/// Requests allowed per minute in each bucket. Everything but GET, HEAD and
+/// OPTIONS counts as a write.
+#[derive(Clone, Copy)]
+pub struct RateLimitConfig {
+    pub reads_per_ip: NonZeroU32,
+    pub writes_per_ip: NonZeroU32,
+    pub writes_per_user: NonZeroU32,
+    /// Take the client address from `Forwarded`/`X-Forwarded-For`. Only safe
+    /// behind a proxy that overwrites those headers.
+    pub trust_proxy_headers: bool,
+}
+
+pub struct RateLimits {
+    reads_per_ip: DefaultKeyedRateLimiter<IpAddr>,
+    writes_per_ip: DefaultKeyedRateLimiter<IpAddr>,
+    writes_per_user: DefaultKeyedRateLimiter<Uuid>,
+    trust_proxy_headers: bool,
+}
+
+impl RateLimits {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimits {
+            reads_per_ip: DefaultKeyedRateLimiter::keyed(Quota::per_minute(config.reads_per_ip)),
+            writes_per_ip: DefaultKeyedRateLimiter::keyed(Quota::per_minute(config.writes_per_ip)),
+            writes_per_user: DefaultKeyedRateLimiter::keyed(Quota::per_minute(
+                config.writes_per_user,
+            )),
+            trust_proxy_headers: config.trust_proxy_headers,
+        }
+    }
+
+    /// Forgets clients whose buckets have refilled, so memory doesn't grow with
+    /// every address ever seen. Meant to be called periodically.
+    pub fn retain_recent(&self) {
+        self.reads_per_ip.retain_recent();
+        self.writes_per_ip.retain_recent();
+        self.writes_per_user.retain_recent();
+    }
+
+    fn client_ip(&self, req: &ServiceRequest) -> Option<IpAddr> {
+        if self.trust_proxy_headers {
+            let info = req.connection_info();
+            let addr = info.realip_remote_addr()?;
+            return addr
+                .parse::<IpAddr>()
+                .ok()
+                .or_else(|| addr.parse::<SocketAddr>().ok().map(|addr| addr.ip()));
+        }
+
+        req.peer_addr().map(|addr| addr.ip())
+    }
+
+    fn check(&self, req: &ServiceRequest) -> Result<(), AppError> {
+        let ip = self.client_ip(req);
+        let is_write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+        if !is_write {
+            return ip.map_or(Ok(()), |ip| check_key(&self.reads_per_ip, &ip));
+        }
+
+        if let Some(ip) = ip {
+            check_key(&self.writes_per_ip, &ip)?;
+        }
+
+        // Only the token is checked here; whether the user still exists is left
+        // to the handler, so a request is never charged a database round trip.
+        if let Ok(claims) = AuthenticatedUser::decode(req.request()) {
+            check_key(&self.writes_per_user, &claims.sub)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_key<K: Clone + Eq + Hash>(
+    limiter: &DefaultKeyedRateLimiter<K>,
+    key: &K,
+) -> Result<(), AppError> {
+    limiter.check_key(key).map_err(|not_until| {
+        let wait = not_until.wait_time_from(DefaultClock::default().now());
+
+        AppError::TooManyRequests {
+            message: "Too many requests, please slow down".into(),
+            retry_after: Some(wait.as_secs_f64().ceil().max(1.0) as i64),
+        }
+    })
+}
+
+/// Rejects requests over the limits in the app's `RateLimits` with 429 and
+/// `Retry-After`. Does nothing when no `RateLimits` is registered.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(limits) = req.app_data::<web::Data<RateLimits>>()
+        && let Err(error) = limits.check(&req)
+    {
+        return Ok(req.error_response(error).map_into_right_body());
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,254 @@
+use lettre::message::SinglePart;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::error::Error;
+
+struct EmailConfig {
+    host: String,
+    from: String,
+    user: String,
+    password: String,
+}
+
+impl EmailConfig {
+    fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            host: env::var("EMAIL_HOST")?,
+            from: env::var("EMAIL_FROM")?,
+            user: env::var("EMAIL_USER")?,
+            password: env::var("EMAIL_PASSWORD")?,
+        })
+    }
+}
+
+async fn send_email(to: &str, subject: &str, html_body: String) -> Result<(), Box<dyn Error>> {
+    let config = EmailConfig::from_env()?;
+
+    let email = Message::builder()
+        .from(config.from.parse()?)
+        .to(to.parse()?)
+        .subject(subject)
+        .singlepart(
+            SinglePart::builder()
+                .header(ContentType::TEXT_HTML)
+                .body(html_body),
+        )?;
+
+    let creds = Credentials::new(config.user, config.password);
+
+    let mailer = AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?
+        .credentials(creds)
+        .build();
+
+    match mailer.send(email).await {
+        Ok(_) => {
+            tracing::info!(to, subject, "Email sent");
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, to, subject, "Failed to send email");
+            return Err("Failed to send email".into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Wraps `content` and `footer` in the branded layout shared by every email.
+fn render(title: &str, content: &str, footer: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"uk\">
+  <head>
+    <meta charset=\"UTF-8\" />
+    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\" />
+    <title>{}</title>
+    <style>
+      body {{
+        margin: 0;
+        padding: 0;
+        font-family: Arial, sans-serif;
+        background-color: #ffffff;
+        color: #333333;
+      }}
+
+      .container {{
+        max-width: 600px;
+        margin: auto;
+        background: #ffffff;
+        border-radius: 12px;
+        overflow: hidden;
+        box-shadow: 0 2px 6px rgba(0, 0, 0, 0.1);
+      }}
+
+      .header {{
+        background: linear-gradient(to right, #f7941d, #fbd38d);
+        text-align: center;
+        padding: 30px 30px 0px;
+      }}
+
+      .header img {{
+        max-width: 250px;
+        height: auto;
+        display: block;
+        margin: 0 auto;
+      }}
+
+      .content {{
+        padding: 20px;
+        text-align: center;
+      }}
+
+      .content h1 {{
+        font-size: 22px;
+        margin-bottom: 10px;
+      }}
+
+      .content p {{
+        font-size: 16px;
+        margin-bottom: 20px;
+      }}
+
+      .btn {{
+        display: inline-block;
+        padding: 12px 24px;
+        background: linear-gradient(to right, #f7941d, #fbd38d);
+        color: #ffffff;
+        text-decoration: none;
+        font-weight: bold;
+        border-radius: 25px;
+        font-size: 16px;
+      }}
+
+      .footer {{
+        font-size: 14px;
+        padding: 0 20px 20px;
+        color: #666666;
+        text-align: center;
+      }}
+
+      @media (max-width: 600px) {{
+        .content h1 {{
+          font-size: 20px;
+        }}
+
+        .content p {{
+          font-size: 15px;
+        }}
+
+        .btn {{
+          font-size: 15px;
+          padding: 10px 20px;
+        }}
+      }}
+    </style>
+  </head>
+  <body>
+    <div class=\"container\">
+      <div class=\"header\">
+        <img
+          src=\"https://marketplace-bucket-mmsj1bcf.s3.eu-central-1.amazonaws.com/email/email.png\"
+          alt=\"Shum маркетплейс\"
+        />
+      </div>
+      <div class=\"content\">
+{}
+      </div>
+      <div class=\"footer\">
+{}
+        <p>З повагою, Команда Shum.</p>
+      </div>
+    </div>
+  </body>
+</html>",
+        title, content, footer
+    )
+}
+
+fn confirmation_link(token: &str) -> Result<String, Box<dyn Error>> {
+    let base_url =
+        env::var("EMAIL_REGISTRATION_URL").map_err(|_| "EMAIL_REGISTRATION_URL must be set")?;
+
+    Ok(format!("{}/{}", base_url, token))
+}
+
+pub(crate) async fn send_confirmation_email(
+    to: &str,
+    first_name: &str,
+    token: &str,
+) -> Result<(), Box<dyn Error>> {
+    let content = format!(
+        "        <h1>Підтвердіть Вашу пошту</h1>
+        <p>Привіт, {}!</p>
+        <p>
+          Дякуємо, що приєдналися до маркетплейсу Shum. Щоб завершити
+          реєстрацію, підтвердіть свою електронну адресу, натиснувши на кнопку
+          нижче.
+        </p>
+        <a href=\"{}\" class=\"btn\">Підтвердити</a>",
+        first_name,
+        confirmation_link(token)?
+    );
+
+    let footer = "        <p>
+          Якщо ви не створювали обліковий запис, просто проігноруйте цей лист.
+        </p>";
+
+    send_email(
+        to,
+        "Confirm your registration",
+        render("Підтвердження пошти", &content, footer),
+    )
+    .await
+}
+
+pub(crate) async fn send_email_change_email(
+    to: &str,
+    first_name: &str,
+    token: &str,
+) -> Result<(), Box<dyn Error>> {
+    let content = format!(
+        "        <h1>Підтвердіть нову пошту</h1>
+        <p>Привіт, {}!</p>
+        <p>
+          Щоб змінити електронну адресу Вашого облікового запису Shum,
+          підтвердіть нову адресу, натиснувши на кнопку нижче.
+        </p>
+        <a href=\"{}\" class=\"btn\">Підтвердити</a>",
+        first_name,
+        confirmation_link(token)?
+    );
+
+    let footer = "        <p>
+          Якщо ви не змінювали адресу, просто проігноруйте цей лист.
+        </p>";
+
+    send_email(
+        to,
+        "Confirm your new email address",
+        render("Зміна пошти", &content, footer),
+    )
+    .await
+}
+
+pub(crate) async fn send_password_reset_email(to: &str, otp: &str) -> Result<(), Box<dyn Error>> {
+    let content = format!(
+        "        <h1>Відновлення пароля</h1>
+        <p>Ваш код для відновлення пароля:</p>
+        <p><strong>{}</strong></p>",
+        otp
+    );
+
+    let footer = "        <p>
+          Якщо ви не запитували відновлення пароля, просто проігноруйте цей
+          лист.
+        </p>";
+
+    send_email(
+        to,
+        "Password reset code",
+        render("Відновлення пароля", &content, footer),
+    )
+    .await
+}
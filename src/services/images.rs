@@ -0,0 +1,130 @@
+use crate::errors::AppError;
+use crate::services::s3::ALLOWED_IMAGE_TYPES;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use once_cell::sync::Lazy;
+use std::env;
+use std::io::Cursor;
+
+/// Smallest width and height accepted for a product photo.
+const MIN_IMAGE_SIDE: u32 = 200;
+/// Largest width and height accepted for a product photo.
+const MAX_IMAGE_SIDE: u32 = 8000;
+/// How many times longer one side may be than the other.
+const MAX_ASPECT_RATIO: u32 = 4;
+/// Quality of re-encoded JPEGs, high enough that the second pass isn't visible.
+const JPEG_QUALITY: u8 = 90;
+
+/// When `UPLOAD_WEBP_QUALITY` (1-100) is set, every photo is stored as a lossy
+/// WebP of that quality instead of in its original format.
+pub static WEBP_QUALITY: Lazy<Option<f32>> = Lazy::new(|| {
+    env::var("UPLOAD_WEBP_QUALITY").ok().map(|value| {
+        value
+            .parse::<f32>()
+            .ok()
+            .filter(|quality| (1.0..=100.0).contains(quality))
+            .expect("UPLOAD_WEBP_QUALITY must be a number from 1 to 100")
+    })
+});
+
+/// A photo as it will be stored.
+pub(crate) struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+impl EncodedImage {
+    pub fn extension(&self) -> &'static str {
+        self.format.extensions_str()[0]
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.format.to_mime_type()
+    }
+}
+
+/// Reads the image header and rejects photos that are too small, too large or
+/// too narrow to show in the product grid. The pixels are not decoded.
+pub(crate) fn validate_dimensions(bytes: &[u8]) -> Result<(), AppError> {
+    let (width, height) = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(AppError::internal)?
+        .into_dimensions()
+        .map_err(|_| AppError::BadRequest("File is not a valid image".into()))?;
+
+    if width < MIN_IMAGE_SIDE || height < MIN_IMAGE_SIDE {
+        return Err(AppError::BadRequest(format!(
+            "Images must be at least {}x{} pixels",
+            MIN_IMAGE_SIDE, MIN_IMAGE_SIDE
+        )));
+    }
+
+    if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+        return Err(AppError::BadRequest(format!(
+            "Images must be at most {}x{} pixels",
+            MAX_IMAGE_SIDE, MAX_IMAGE_SIDE
+        )));
+    }
+
+    if width.max(height) > width.min(height) * MAX_ASPECT_RATIO {
+        return Err(AppError::BadRequest(format!(
+            "Images can't be more than {} times wider than tall or the other way round",
+            MAX_ASPECT_RATIO
+        )));
+    }
+
+    Ok(())
+}
+
+/// Decodes and re-encodes the photo so EXIF and other metadata, such as the GPS
+/// position a phone records, never reach the public bucket. The EXIF orientation
+/// is applied to the pixels first, so the result still displays upright without
+/// it. Jpeg and png photos keep their format unless `WEBP_QUALITY` is set;
+/// other allowed types are always stored as WebP.
+pub(crate) fn strip_metadata(bytes: &[u8]) -> Result<EncodedImage, AppError> {
+    let invalid = |_| AppError::BadRequest("File is not a valid image".into());
+
+    let reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(AppError::internal)?;
+    let Some(format) = reader
+        .format()
+        .filter(|format| ALLOWED_IMAGE_TYPES.contains(format))
+    else {
+        return Err(AppError::BadRequest("Invalid file type".into()));
+    };
+    let mut decoder = reader.into_decoder().map_err(invalid)?;
+    let orientation = decoder.orientation().map_err(invalid)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+    image.apply_orientation(orientation);
+
+    if let Some(quality) = *WEBP_QUALITY {
+        let rgba = image.to_rgba8();
+        let encoded =
+            webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height()).encode(quality);
+
+        return Ok(EncodedImage {
+            bytes: encoded.to_vec(),
+            format: ImageFormat::WebP,
+        });
+    }
+
+    let format = match format {
+        ImageFormat::Jpeg | ImageFormat::Png => format,
+        _ => ImageFormat::WebP,
+    };
+    let mut encoded = Vec::new();
+    match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)),
+        ImageFormat::Png => image.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_to(&mut Cursor::new(&mut encoded), ImageFormat::WebP),
+    }
+    .map_err(AppError::internal)?;
+
+    Ok(EncodedImage {
+        bytes: encoded,
+        format,
+    })
+}
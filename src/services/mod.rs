@@ -1 +1,6 @@
+pub mod email;
+pub mod images;
+pub mod password;
 pub mod s3;
+pub mod token;
+pub mod videos;
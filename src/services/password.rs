@@ -0,0 +1,139 @@
+use crate::errors::AppError;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::env;
+
+/// Argon2id cost of new password hashes, read once at startup. Existing hashes
+/// keep verifying with the parameters stored in them.
+#[derive(Clone)]
+pub struct PasswordConfig {
+    params: Params,
+}
+
+fn cost_from_env(name: &str, default: u32) -> Result<u32, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse::<u32>()
+            .ok()
+            .filter(|cost| *cost > 0)
+            .ok_or_else(|| format!("{} must be a positive number", name)),
+        Err(_) => Ok(default),
+    }
+}
+
+impl PasswordConfig {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, String> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+
+        Ok(Self { params })
+    }
+
+    /// Defaults to the argon2 crate's recommendation of 19 MiB, 2 iterations
+    /// and 1 lane.
+    pub fn from_env() -> Result<Self, String> {
+        Self::new(
+            cost_from_env("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?,
+            cost_from_env("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?,
+            cost_from_env("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?,
+        )
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, AppError> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(AppError::internal)
+    }
+
+    pub fn verify(&self, password: &str, hash: &str) -> Result<bool, AppError> {
+        let hash = PasswordHash::new(hash).map_err(AppError::internal)?;
+
+        Ok(self
+            .argon2()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    }
+
+    /// Whether `hash` is cheaper to brute-force than a new one would be. Hashes
+    /// that are costlier than the current settings are left alone.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        match Params::try_from(&hash) {
+            Ok(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_what_it_hashes() {
+        let passwords = PasswordConfig::new(1024, 1, 1).unwrap();
+        let hash = passwords.hash("correct horse").unwrap();
+
+        assert!(passwords.verify("correct horse", &hash).unwrap());
+        assert!(!passwords.verify("battery staple", &hash).unwrap());
+    }
+
+    #[test]
+    fn hashes_made_with_other_settings_still_verify() {
+        let old = PasswordConfig::new(1024, 1, 1).unwrap();
+        let hash = old.hash("correct horse").unwrap();
+
+        let current = PasswordConfig::new(2048, 2, 1).unwrap();
+        assert!(current.verify("correct horse", &hash).unwrap());
+    }
+
+    #[test]
+    fn only_weaker_hashes_need_a_rehash() {
+        let current = PasswordConfig::new(2048, 2, 1).unwrap();
+
+        for (memory_kib, iterations) in [(1024, 2), (2048, 1)] {
+            let weaker = PasswordConfig::new(memory_kib, iterations, 1).unwrap();
+            assert!(current.needs_rehash(&weaker.hash("pw").unwrap()));
+        }
+        for (memory_kib, iterations) in [(2048, 2), (4096, 3)] {
+            let same_or_stronger = PasswordConfig::new(memory_kib, iterations, 1).unwrap();
+            assert!(!current.needs_rehash(&same_or_stronger.hash("pw").unwrap()));
+        }
+    }
+
+    #[test]
+    fn other_argon2_variants_need_a_rehash() {
+        let current = PasswordConfig::new(1024, 1, 1).unwrap();
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2i = Argon2::new(
+            Algorithm::Argon2i,
+            Version::V0x13,
+            Params::new(1024, 1, 1, None).unwrap(),
+        )
+        .hash_password(b"pw", &salt)
+        .unwrap()
+        .to_string();
+
+        assert!(current.needs_rehash(&argon2i));
+    }
+}
@@ -1,13 +1,92 @@
+use crate::errors::AppError;
 use aws_config::BehaviorVersion;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_types::region::Region;
+use image::ImageFormat;
 use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
 use std::env;
-use uuid::Uuid;
 
-pub(crate) const MAX_FILE_SIZE: usize = 5 * 1024 * 1024;
+const DEFAULT_MAX_UPLOAD_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest single file accepted for upload, from `MAX_UPLOAD_BYTES` (5 MB by default).
+pub static MAX_UPLOAD_BYTES: Lazy<usize> = Lazy::new(|| match env::var("MAX_UPLOAD_BYTES") {
+    Ok(value) => value
+        .parse::<usize>()
+        .ok()
+        .filter(|bytes| *bytes > 0)
+        .expect("MAX_UPLOAD_BYTES must be a positive number of bytes"),
+    Err(_) => DEFAULT_MAX_UPLOAD_BYTES,
+});
+
+const DEFAULT_MAX_REQUEST_UPLOAD_BYTES: usize = 50 * 1024 * 1024;
+
+/// Most bytes read from one multipart request, files and text fields together,
+/// from `MAX_REQUEST_UPLOAD_BYTES` (50 MB by default, room for a video and photos).
+pub static MAX_REQUEST_UPLOAD_BYTES: Lazy<usize> =
+    Lazy::new(|| match env::var("MAX_REQUEST_UPLOAD_BYTES") {
+        Ok(value) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|bytes| *bytes > 0)
+            .expect("MAX_REQUEST_UPLOAD_BYTES must be a positive number of bytes"),
+        Err(_) => DEFAULT_MAX_REQUEST_UPLOAD_BYTES,
+    });
+
+const DEFAULT_ALLOWED_IMAGE_TYPES: &str = "image/jpeg,image/png,image/webp";
+
+/// Photo types accepted by every upload path, from the comma separated
+/// `ALLOWED_IMAGE_TYPES` (jpeg, png and webp by default).
+pub static ALLOWED_IMAGE_TYPES: Lazy<Vec<ImageFormat>> = Lazy::new(|| {
+    let value =
+        env::var("ALLOWED_IMAGE_TYPES").unwrap_or_else(|_| DEFAULT_ALLOWED_IMAGE_TYPES.to_string());
+
+    parse_image_types(&value).unwrap_or_else(|e| panic!("{}", e))
+});
+
+/// `image/jpg` isn't a registered type, but clients send it for jpegs.
+fn image_format(mime: &str) -> Option<ImageFormat> {
+    match mime.trim().to_ascii_lowercase().as_str() {
+        "image/jpg" => Some(ImageFormat::Jpeg),
+        mime => ImageFormat::from_mime_type(mime),
+    }
+}
+
+/// Photos are decoded before they are stored, so only types this build of the
+/// `image` crate can read may be listed.
+fn parse_image_types(value: &str) -> Result<Vec<ImageFormat>, String> {
+    let mut formats = Vec::new();
+    for mime in value.split(',').filter(|mime| !mime.trim().is_empty()) {
+        let format = image_format(mime)
+            .filter(|format| format.reading_enabled())
+            .ok_or_else(|| {
+                format!(
+                    "ALLOWED_IMAGE_TYPES lists {:?}, which can't be decoded",
+                    mime.trim()
+                )
+            })?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+
+    if formats.is_empty() {
+        return Err("ALLOWED_IMAGE_TYPES must list at least one image type".to_string());
+    }
+
+    Ok(formats)
+}
+
+fn allows(allowed: &[ImageFormat], mime: &str) -> bool {
+    image_format(mime).is_some_and(|format| allowed.contains(&format))
+}
+
+/// Whether photos of this MIME type may be uploaded.
+pub(crate) fn is_allowed_image_type(mime: &str) -> bool {
+    allows(&ALLOWED_IMAGE_TYPES, mime)
+}
 
 pub static AWS_MARKETPLACE_BUCKET: Lazy<String> =
     Lazy::new(|| env::var("AWS_MARKETPLACE_BUCKET").expect("AWS_MARKETPLACE_BUCKET not set"));
@@ -15,11 +94,30 @@ pub static AWS_MARKETPLACE_BUCKET: Lazy<String> =
 pub static AWS_REGION: Lazy<String> =
     Lazy::new(|| env::var("AWS_REGION").expect("AWS_REGION not set"));
 
-pub(crate) async fn upload_to_s3(
-    bucket: &str,
-    file_bytes: Vec<u8>,
-    filename: &str,
-) -> Result<String, actix_web::Error> {
+/// Optional CDN in front of the bucket, e.g. `https://media.example.com`.
+pub static MEDIA_BASE_URL: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("MEDIA_BASE_URL")
+        .ok()
+        .map(|url| url.trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+});
+
+/// Public url of an object in the marketplace bucket, served through
+/// `MEDIA_BASE_URL` when it is set.
+pub(crate) fn build_s3_url(key: &str) -> String {
+    if let Some(base_url) = MEDIA_BASE_URL.as_deref() {
+        return format!("{}/{}", base_url, key);
+    }
+
+    format!(
+        "https://{}.s3.{}.amazonaws.com/{}",
+        AWS_MARKETPLACE_BUCKET.as_str(),
+        AWS_REGION.as_str(),
+        key
+    )
+}
+
+async fn s3_client() -> Client {
     let region_provider = RegionProviderChain::first_try(Some(Region::new(AWS_REGION.as_str())))
         .or_default_provider();
 
@@ -28,29 +126,111 @@ pub(crate) async fn upload_to_s3(
         .load()
         .await;
 
-    let client = Client::new(&config);
+    Client::new(&config)
+}
+
+/// Stores the file under `uploads/{sha256}.{extension}`, so identical content
+/// always maps to one object. Uploading content that is already stored just
+/// returns its url.
+pub(crate) async fn upload_to_s3(
+    file_bytes: Vec<u8>,
+    extension: &str,
+    content_type: &str,
+) -> Result<String, AppError> {
+    let client = s3_client().await;
 
     let key = format!(
-        "uploads/{}-{}",
-        Uuid::new_v4(),
-        sanitize_filename::sanitize(filename)
+        "uploads/{}.{}",
+        hex::encode(Sha256::digest(&file_bytes)),
+        extension
     );
 
+    let existing = client
+        .head_object()
+        .bucket(AWS_MARKETPLACE_BUCKET.as_str())
+        .key(&key)
+        .send()
+        .await;
+
+    if existing.is_ok() {
+        return Ok(build_s3_url(&key));
+    }
+
     let body = ByteStream::from(file_bytes);
 
     client
         .put_object()
-        .bucket(bucket)
+        .bucket(AWS_MARKETPLACE_BUCKET.as_str())
         .key(&key)
+        .content_type(content_type)
         .body(body)
         .send()
         .await
         .map_err(|e| {
-            eprintln!("S3 Upload Error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to upload to S3")
+            tracing::error!(error = %e, "S3 upload failed");
+            AppError::Internal
         })?;
 
-    let url = format!("https://{}.s3.amazonaws.com/{}", bucket, key);
+    Ok(build_s3_url(&key))
+}
+
+pub(crate) fn key_from_url(url: &str) -> Option<&str> {
+    if let Some(base_url) = MEDIA_BASE_URL.as_deref()
+        && let Some(key) = url
+            .strip_prefix(base_url)
+            .and_then(|rest| rest.strip_prefix('/'))
+    {
+        return Some(key);
+    }
+
+    url.split_once(".amazonaws.com/").map(|(_, key)| key)
+}
+
+pub(crate) async fn delete_from_s3(bucket: &str, key: &str) -> Result<(), AppError> {
+    let client = s3_client().await;
+
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, key, "S3 delete failed");
+            AppError::Internal
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_list_accepts_jpeg_png_and_webp() {
+        let allowed = parse_image_types(DEFAULT_ALLOWED_IMAGE_TYPES).unwrap();
+
+        for mime in ["image/jpeg", "image/jpg", "IMAGE/PNG", "image/webp"] {
+            assert!(allows(&allowed, mime), "{}", mime);
+        }
+        for mime in ["image/gif", "image/heic", "application/pdf", "", "jpeg"] {
+            assert!(!allows(&allowed, mime), "{}", mime);
+        }
+    }
+
+    #[test]
+    fn the_list_can_be_narrowed() {
+        let allowed = parse_image_types(" image/png ,, image/png").unwrap();
+
+        assert_eq!(allowed, [ImageFormat::Png]);
+        assert!(!allows(&allowed, "image/jpeg"));
+    }
 
-    Ok(url)
+    #[test]
+    fn rejects_types_that_cant_be_decoded() {
+        assert!(parse_image_types("image/jpeg,image/gif").is_err());
+        assert!(parse_image_types("text/plain").is_err());
+        assert!(parse_image_types(" , ").is_err());
+    }
 }
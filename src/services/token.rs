@@ -0,0 +1,304 @@
+use crate::errors::AppError;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::env;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const DEFAULT_ACCESS_TOKEN_TTL_SECS: i64 = 15 * 60;
+const DEFAULT_REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+const DEFAULT_CONFIRMATION_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+const DEFAULT_OTP_TTL_SECS: i64 = 15 * 60;
+const MIN_JWT_SECRET_LENGTH: usize = 32;
+/// OTPs are six decimal digits.
+const OTP_MODULUS: u32 = 1_000_000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    #[default]
+    Access,
+    Refresh,
+    Confirmation,
+    EmailChange,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Claims {
+    pub sub: Uuid,
+    pub email: String,
+    pub exp: usize,
+    #[serde(default)]
+    pub token_type: TokenType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
+    /// Lets clients show admin tools. The server doesn't trust it: `AdminUser`
+    /// checks the database.
+    #[serde(default)]
+    pub is_admin: bool,
+}
+
+/// What a new token says about its user. The expiry follows from `token_type`.
+pub struct TokenInput<'a> {
+    pub user_id: Uuid,
+    pub email: &'a str,
+    pub token_type: TokenType,
+    pub is_admin: bool,
+}
+
+/// The signing secret and token lifetimes, read once at startup.
+#[derive(Clone)]
+pub struct JwtConfig {
+    secret: String,
+    access_token_ttl: chrono::Duration,
+    refresh_token_ttl: chrono::Duration,
+    confirmation_ttl: chrono::Duration,
+    otp_ttl: chrono::Duration,
+}
+
+fn ttl_from_env(name: &str, default_secs: i64) -> Result<chrono::Duration, String> {
+    let secs = match env::var(name) {
+        Ok(value) => value
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| format!("{} must be a positive number of seconds", name))?,
+        Err(_) => default_secs,
+    };
+
+    Ok(chrono::Duration::seconds(secs))
+}
+
+impl JwtConfig {
+    /// A config with the default lifetimes.
+    pub fn new(secret: String) -> Result<Self, String> {
+        if secret.len() < MIN_JWT_SECRET_LENGTH {
+            return Err(format!(
+                "JWT_SECRET must be at least {} bytes long",
+                MIN_JWT_SECRET_LENGTH
+            ));
+        }
+
+        Ok(Self {
+            secret,
+            access_token_ttl: chrono::Duration::seconds(DEFAULT_ACCESS_TOKEN_TTL_SECS),
+            refresh_token_ttl: chrono::Duration::seconds(DEFAULT_REFRESH_TOKEN_TTL_SECS),
+            confirmation_ttl: chrono::Duration::seconds(DEFAULT_CONFIRMATION_TTL_SECS),
+            otp_ttl: chrono::Duration::seconds(DEFAULT_OTP_TTL_SECS),
+        })
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set".to_string())?;
+
+        Ok(Self {
+            access_token_ttl: ttl_from_env("ACCESS_TOKEN_TTL", DEFAULT_ACCESS_TOKEN_TTL_SECS)?,
+            refresh_token_ttl: ttl_from_env("REFRESH_TOKEN_TTL", DEFAULT_REFRESH_TOKEN_TTL_SECS)?,
+            confirmation_ttl: ttl_from_env("CONFIRMATION_TTL", DEFAULT_CONFIRMATION_TTL_SECS)?,
+            otp_ttl: ttl_from_env("OTP_TTL", DEFAULT_OTP_TTL_SECS)?,
+            ..Self::new(secret)?
+        })
+    }
+
+    fn lifetime(&self, token_type: TokenType) -> chrono::Duration {
+        match token_type {
+            TokenType::Access => self.access_token_ttl,
+            TokenType::Refresh => self.refresh_token_ttl,
+            TokenType::Confirmation | TokenType::EmailChange => self.confirmation_ttl,
+        }
+    }
+
+    /// When a token of `token_type` created now expires.
+    pub fn expires_at(&self, token_type: TokenType) -> DateTime<Utc> {
+        Utc::now() + self.lifetime(token_type)
+    }
+
+    pub fn otp_ttl(&self) -> chrono::Duration {
+        self.otp_ttl
+    }
+
+    pub fn create_token(&self, input: TokenInput<'_>) -> Result<String, AppError> {
+        let claims = Claims {
+            sub: input.user_id,
+            email: input.email.to_string(),
+            exp: self.expires_at(input.token_type).timestamp() as usize,
+            token_type: input.token_type,
+            jti: Some(Uuid::new_v4()),
+            is_admin: input.is_admin,
+        };
+
+        let key = EncodingKey::from_secret(self.secret.as_bytes());
+        encode(&Header::default(), &claims, &key).map_err(AppError::internal)
+    }
+
+    /// Checks the signature and expiry of any token issued by this API. Every
+    /// endpoint that accepts a token goes through here so they agree on the
+    /// algorithm and leeway; callers check `token_type` themselves.
+    pub fn decode_token(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+
+        let key = DecodingKey::from_secret(self.secret.as_bytes());
+        decode::<Claims>(token, &key, &validation).map(|data| data.claims)
+    }
+
+    pub fn decode_access_token(&self, token: &str) -> Result<Claims, AppError> {
+        match self.decode_token(token) {
+            Ok(claims) if claims.token_type == TokenType::Access => Ok(claims),
+            _ => Err(AppError::Unauthorized("Invalid token".into())),
+        }
+    }
+
+    /// HMAC of a one-time code, bound to its user. Only this is stored, so a
+    /// leaked `otp_tokens` table doesn't reveal live codes.
+    fn otp_mac(&self, user_id: Uuid, otp: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(user_id.as_bytes());
+        mac.update(otp.as_bytes());
+        mac
+    }
+
+    pub fn hash_otp(&self, user_id: Uuid, otp: &str) -> String {
+        hex::encode(self.otp_mac(user_id, otp).finalize().into_bytes())
+    }
+
+    /// Compares in constant time.
+    pub fn verify_otp(&self, user_id: Uuid, otp: &str, otp_hash: &str) -> bool {
+        hex::decode(otp_hash)
+            .is_ok_and(|expected| self.otp_mac(user_id, otp).verify_slice(&expected).is_ok())
+    }
+}
+
+pub fn generate_otp() -> String {
+    // Rejection sampling keeps every code equally likely.
+    let limit = u32::MAX - u32::MAX % OTP_MODULUS;
+    loop {
+        let value = OsRng.next_u32();
+        if value < limit {
+            return format!("{:06}", value % OTP_MODULUS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(secret: &str) -> JwtConfig {
+        JwtConfig::new(secret.to_string()).unwrap()
+    }
+
+    fn input(token_type: TokenType) -> TokenInput<'static> {
+        TokenInput {
+            user_id: Uuid::nil(),
+            email: "user@example.com",
+            token_type,
+            is_admin: true,
+        }
+    }
+
+    #[test]
+    fn decodes_what_it_creates() {
+        let jwt = config("0123456789abcdef0123456789abcdef");
+        let token = jwt.create_token(input(TokenType::Refresh)).unwrap();
+        let claims = jwt.decode_token(&token).unwrap();
+
+        assert_eq!(claims.sub, Uuid::nil());
+        assert_eq!(claims.email, "user@example.com");
+        assert_eq!(claims.token_type, TokenType::Refresh);
+        assert!(claims.is_admin);
+        assert!(claims.jti.is_some());
+        assert!(claims.exp as i64 > Utc::now().timestamp() + 29 * 24 * 60 * 60);
+    }
+
+    #[test]
+    fn rejects_tokens_signed_with_another_secret() {
+        let token = config("0123456789abcdef0123456789abcdef")
+            .create_token(input(TokenType::Access))
+            .unwrap();
+
+        assert!(
+            config("fedcba9876543210fedcba9876543210")
+                .decode_token(&token)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let jwt = config("0123456789abcdef0123456789abcdef");
+        let claims = Claims {
+            sub: Uuid::nil(),
+            email: "user@example.com".into(),
+            exp: (Utc::now().timestamp() - 1) as usize,
+            token_type: TokenType::Access,
+            jti: None,
+            is_admin: false,
+        };
+        let key = EncodingKey::from_secret(jwt.secret.as_bytes());
+        let token = encode(&Header::default(), &claims, &key).unwrap();
+
+        let error = jwt.decode_token(&token).unwrap_err();
+        assert_eq!(
+            *error.kind(),
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature
+        );
+    }
+
+    #[test]
+    fn access_tokens_must_be_access_tokens() {
+        let jwt = config("0123456789abcdef0123456789abcdef");
+
+        let access = jwt.create_token(input(TokenType::Access)).unwrap();
+        assert!(jwt.decode_access_token(&access).is_ok());
+
+        for token_type in [
+            TokenType::Refresh,
+            TokenType::Confirmation,
+            TokenType::EmailChange,
+        ] {
+            let token = jwt.create_token(input(token_type)).unwrap();
+            assert!(jwt.decode_access_token(&token).is_err());
+        }
+    }
+
+    #[test]
+    fn tokens_without_a_type_are_access_tokens() {
+        let jwt = config("0123456789abcdef0123456789abcdef");
+        let claims = serde_json::json!({
+            "sub": Uuid::nil(),
+            "email": "user@example.com",
+            "exp": Utc::now().timestamp() + 60,
+        });
+        let key = EncodingKey::from_secret(jwt.secret.as_bytes());
+        let token = encode(&Header::default(), &claims, &key).unwrap();
+
+        assert!(jwt.decode_access_token(&token).is_ok());
+    }
+
+    #[test]
+    fn otp_hashes_verify_only_for_the_same_code_and_user() {
+        let jwt = config("0123456789abcdef0123456789abcdef");
+        let user_id = Uuid::new_v4();
+        let hash = jwt.hash_otp(user_id, "123456");
+
+        assert!(jwt.verify_otp(user_id, "123456", &hash));
+        assert!(!jwt.verify_otp(user_id, "654321", &hash));
+        assert!(!jwt.verify_otp(Uuid::new_v4(), "123456", &hash));
+        assert!(!jwt.verify_otp(user_id, "123456", "not hex"));
+    }
+
+    #[test]
+    fn otps_are_six_digits() {
+        for _ in 0..100 {
+            let otp = generate_otp();
+            assert_eq!(otp.len(), 6);
+            assert!(otp.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::errors::AppError;
+use once_cell::sync::Lazy;
+use std::env;
+
+const DEFAULT_MAX_VIDEO_BYTES: usize = 20 * 1024 * 1024;
+
+/// Largest product video accepted, from `MAX_VIDEO_BYTES` (20 MB by default).
+pub static MAX_VIDEO_BYTES: Lazy<usize> = Lazy::new(|| match env::var("MAX_VIDEO_BYTES") {
+    Ok(value) => value
+        .parse::<usize>()
+        .ok()
+        .filter(|bytes| *bytes > 0)
+        .expect("MAX_VIDEO_BYTES must be a positive number of bytes"),
+    Err(_) => DEFAULT_MAX_VIDEO_BYTES,
+});
+
+/// MP4 major brands of ordinary video files. HEIC/AVIF photos and QuickTime
+/// movies share the container but not these brands.
+const MP4_BRANDS: &[&[u8]] = &[b"iso", b"mp4", b"avc1", b"M4V", b"dash"];
+
+/// How far into a WebM file its EBML header may name the document type.
+const WEBM_DOCTYPE_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VideoFormat {
+    Mp4,
+    WebM,
+}
+
+impl VideoFormat {
+    /// The format `bytes` are in, judging by their first bytes rather than by
+    /// the file name or the type the client claims.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() >= 12
+            && &bytes[4..8] == b"ftyp"
+            && MP4_BRANDS
+                .iter()
+                .any(|brand| bytes[8..12].starts_with(brand))
+        {
+            return Some(VideoFormat::Mp4);
+        }
+
+        if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3])
+            && bytes[..bytes.len().min(WEBM_DOCTYPE_WINDOW)]
+                .windows(4)
+                .any(|window| window == b"webm")
+        {
+            return Some(VideoFormat::WebM);
+        }
+
+        None
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "mp4",
+            VideoFormat::WebM => "webm",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "video/mp4",
+            VideoFormat::WebM => "video/webm",
+        }
+    }
+}
+
+/// A product video as it will be stored.
+pub(crate) struct EncodedVideo {
+    pub bytes: Vec<u8>,
+    pub format: VideoFormat,
+}
+
+impl EncodedVideo {
+    pub fn new(bytes: Vec<u8>) -> Result<Self, AppError> {
+        let format = VideoFormat::sniff(&bytes)
+            .ok_or_else(|| AppError::BadRequest("Videos must be mp4 or webm files".into()))?;
+
+        Ok(Self { bytes, format })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4(brand: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0, 0x20];
+        bytes.extend_from_slice(b"ftyp");
+        bytes.extend_from_slice(brand);
+        bytes.extend_from_slice(&[0; 20]);
+        bytes
+    }
+
+    #[test]
+    fn recognizes_mp4_by_its_brand() {
+        assert_eq!(VideoFormat::sniff(&mp4(b"isom")), Some(VideoFormat::Mp4));
+        assert_eq!(VideoFormat::sniff(&mp4(b"mp42")), Some(VideoFormat::Mp4));
+        assert_eq!(VideoFormat::sniff(&mp4(b"heic")), None);
+        assert_eq!(VideoFormat::sniff(&mp4(b"qt  ")), None);
+    }
+
+    #[test]
+    fn recognizes_webm_but_not_other_matroska() {
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+        webm.extend_from_slice(b"webm");
+        assert_eq!(VideoFormat::sniff(&webm), Some(VideoFormat::WebM));
+
+        let mut matroska = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x88];
+        matroska.extend_from_slice(b"matroska");
+        assert_eq!(VideoFormat::sniff(&matroska), None);
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        assert_eq!(VideoFormat::sniff(b""), None);
+        assert_eq!(VideoFormat::sniff(b"\x89PNG\r\n\x1a\n0000000000"), None);
+        assert!(EncodedVideo::new(b"not a video".to_vec()).is_err());
+    }
+}
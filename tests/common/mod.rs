@@ -0,0 +1,94 @@
+//! Shared setup for the end-to-end tests. `sqlx::test` creates a fresh,
+//! migrated database for each test, so `DATABASE_URL` must point at a Postgres
+//! server the user can create databases on.
+
+// Each test binary compiles its own copy and uses only part of it.
+#![allow(dead_code)]
+
+use actix_web::HttpServer;
+use actix_web::dev::ServerHandle;
+use actix_web::web;
+use marketplace_api::app::{AppState, build_app};
+use marketplace_api::handlers::auth::SignupConfig;
+use marketplace_api::middleware::rate_limit::{RateLimitConfig, RateLimits};
+use marketplace_api::services::password::PasswordConfig;
+use marketplace_api::services::token::{JwtConfig, TokenInput, TokenType};
+use sqlx::PgPool;
+use std::net::TcpListener;
+use std::num::NonZeroU32;
+use uuid::Uuid;
+
+pub const JWT_SECRET: &str = "integration-test-secret-0123456789abcdef";
+
+/// A server on a random local port, backed by the database `sqlx::test` made
+/// for the test.
+pub struct TestServer {
+    pub address: String,
+    handle: ServerHandle,
+}
+
+impl Drop for TestServer {
+    /// Stops the server so its connections go back to `sqlx::test`'s shared
+    /// pool instead of piling up across tests.
+    fn drop(&mut self) {
+        drop(self.handle.stop(false));
+    }
+}
+
+pub fn jwt_config() -> JwtConfig {
+    JwtConfig::new(JWT_SECRET.to_string()).unwrap()
+}
+
+/// Inserts a confirmed user and returns its id with an access token for it.
+pub async fn create_user(pool: &PgPool, email: &str) -> (Uuid, String) {
+    let user_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO users (first_name, last_name, email, password, active)
+         VALUES ('Test', 'User', $1, 'not a hash', true)
+         RETURNING id",
+    )
+    .bind(email)
+    .fetch_one(pool)
+    .await
+    .unwrap();
+
+    let token = jwt_config()
+        .create_token(TokenInput {
+            user_id,
+            email,
+            token_type: TokenType::Access,
+            is_admin: false,
+        })
+        .unwrap();
+
+    (user_id, token)
+}
+
+pub fn spawn_server(pool: PgPool) -> TestServer {
+    let limit = NonZeroU32::new(1000).unwrap();
+    let state = AppState {
+        pool,
+        jwt_config: jwt_config(),
+        password_config: PasswordConfig::new(1024, 1, 1).unwrap(),
+        signup_config: SignupConfig::default(),
+        rate_limits: web::Data::new(RateLimits::new(RateLimitConfig {
+            reads_per_ip: limit,
+            writes_per_ip: limit,
+            writes_per_user: limit,
+            trust_proxy_headers: false,
+        })),
+        dev_mode: true,
+        allowed_origins: Vec::new(),
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || build_app(state.clone()))
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+    let handle = server.handle();
+    tokio::spawn(server);
+
+    TestServer { address, handle }
+}
@@ -0,0 +1,30 @@
+//! End-to-end tests of account deletion.
+
+mod common;
+
+use common::{create_user, spawn_server};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn deleting_an_account_twice_succeeds_both_times(pool: PgPool) {
+    let (user_id, token) = create_user(&pool, "user@example.com").await;
+    let server = spawn_server(pool.clone());
+    let client = reqwest::Client::new();
+
+    for _ in 0..2 {
+        let response = client
+            .delete(format!("{}/api/v1/users/me", server.address))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+}
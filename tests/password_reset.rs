@@ -0,0 +1,55 @@
+//! End-to-end tests of the password reset flow.
+
+mod common;
+
+use common::spawn_server;
+use serde_json::json;
+use sqlx::PgPool;
+
+const EMAIL: &str = "buyer@example.com";
+
+async fn insert_user(pool: &PgPool) {
+    sqlx::query(
+        "INSERT INTO users (first_name, last_name, email, password, active)
+         VALUES ('Test', 'Buyer', $1, 'not a hash', true)",
+    )
+    .bind(EMAIL)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[sqlx::test]
+async fn requesting_a_new_code_does_not_lift_the_lockout(pool: PgPool) {
+    insert_user(&pool).await;
+    let server = spawn_server(pool);
+    let client = reqwest::Client::new();
+
+    let request_code = || async {
+        let response = client
+            .post(format!("{}/api/v1/auth/reset-password", server.address))
+            .json(&json!({ "email": EMAIL }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    };
+    let guess = || async {
+        client
+            .post(format!("{}/api/v1/auth/otp", server.address))
+            .json(&json!({ "email": EMAIL, "otp": "not a code" }))
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    request_code().await;
+    for _ in 0..5 {
+        assert_eq!(guess().await, 401);
+    }
+    assert_eq!(guess().await, 429);
+
+    request_code().await;
+    assert_eq!(guess().await, 429);
+}
@@ -0,0 +1,60 @@
+//! End-to-end tests of seller reviews.
+
+mod common;
+
+use common::{create_user, spawn_server};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn a_blocked_user_cannot_review_the_blocker(pool: PgPool) {
+    let (seller_id, seller_token) = create_user(&pool, "seller@example.com").await;
+    let (buyer_id, buyer_token) = create_user(&pool, "buyer@example.com").await;
+    let server = spawn_server(pool);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!(
+            "{}/api/v1/users/{}/block",
+            server.address, buyer_id
+        ))
+        .bearer_auth(&seller_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!(
+            "{}/api/v1/users/{}/reviews",
+            server.address, seller_id
+        ))
+        .bearer_auth(&buyer_token)
+        .json(&json!({ "rating": 1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+}
+
+#[sqlx::test]
+async fn creating_a_review_returns_201_with_its_id(pool: PgPool) {
+    let (seller_id, _) = create_user(&pool, "seller@example.com").await;
+    let (_, buyer_token) = create_user(&pool, "buyer@example.com").await;
+    let server = spawn_server(pool);
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/api/v1/users/{}/reviews",
+            server.address, seller_id
+        ))
+        .bearer_auth(&buyer_token)
+        .json(&json!({ "rating": 5, "comment": "Fast shipping" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["id"].is_i64());
+}
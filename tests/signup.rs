@@ -0,0 +1,29 @@
+//! End-to-end tests of registration.
+
+mod common;
+
+use common::spawn_server;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn signup_does_not_return_the_confirmation_token(pool: PgPool) {
+    let server = spawn_server(pool);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/auth/register", server.address))
+        .json(&json!({
+            "first_name": "Test",
+            "last_name": "User",
+            "email": "new@example.com",
+            "password": "Str0ng-password!"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert!(body.get("token").is_none());
+    assert!(body.get("refresh_token").is_none());
+}